@@ -0,0 +1,468 @@
+//! Statistics over the sessions of the active project, for the stats page.
+//!
+//! Dates, weekdays and hours are in local time. A session counts towards the
+//! day, weekday and hour it started in; markers count towards the hour they
+//! were placed in.
+
+use crate::{
+    session::Session,
+    storage::{csv_field, ExportFormat},
+};
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike, Utc, Weekday};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStats {
+    /// First and last day included, if limited.
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub session_count: usize,
+    pub total_recorded_ms: i64,
+    pub average_length_ms: i64,
+    pub marker_count: usize,
+    pub markers_per_hour: f64,
+    /// Monday first.
+    pub weekdays: Vec<WeekdayStats>,
+    /// Hours 0 to 23.
+    pub hours: Vec<HourStats>,
+    pub busiest_weekday: Option<Weekday>,
+    pub busiest_hour: Option<u32>,
+    /// Most frequent first.
+    pub labels: Vec<Frequency>,
+    pub categories: Vec<Frequency>,
+    /// Longest run of consecutive days with at least one session.
+    pub longest_streak: Option<Streak>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WeekdayStats {
+    pub weekday: Weekday,
+    pub sessions: usize,
+    pub recorded_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HourStats {
+    pub hour: u32,
+    pub sessions: usize,
+    pub markers: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Frequency {
+    pub value: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Streak {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: u32,
+}
+
+fn local(time: DateTime<Utc>) -> DateTime<Local> {
+    time.with_timezone(&Local)
+}
+
+/// Values by how often they occur, most frequent first, then alphabetically.
+fn frequencies<'a>(values: impl Iterator<Item = &'a str>) -> Vec<Frequency> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+
+    let mut frequencies: Vec<Frequency> = counts
+        .into_iter()
+        .map(|(value, count)| Frequency {
+            value: value.to_string(),
+            count,
+        })
+        .collect();
+    frequencies.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    frequencies
+}
+
+fn longest_streak(days: &BTreeSet<NaiveDate>) -> Option<Streak> {
+    let mut longest: Option<Streak> = None;
+    let mut current: Option<Streak> = None;
+
+    for &day in days {
+        let streak = match current {
+            Some(streak) if streak.end.succ_opt() == Some(day) => Streak {
+                end: day,
+                days: streak.days + 1,
+                ..streak
+            },
+            _ => Streak {
+                start: day,
+                end: day,
+                days: 1,
+            },
+        };
+
+        if longest.as_ref().is_none_or(|longest| streak.days > longest.days) {
+            longest = Some(streak.clone());
+        }
+        current = Some(streak);
+    }
+
+    longest
+}
+
+/// Statistics of the `sessions` that started between `from` and `to`, both
+/// inclusive.
+pub fn compute(
+    sessions: &[Session],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> SessionStats {
+    let sessions: Vec<&Session> = sessions
+        .iter()
+        .filter(|session| {
+            let day = local(session.start_time).date_naive();
+            from.is_none_or(|from| day >= from) && to.is_none_or(|to| day <= to)
+        })
+        .collect();
+
+    let total_recorded_ms: i64 = sessions
+        .iter()
+        .map(|session| session.duration().num_milliseconds().max(0))
+        .sum();
+    let marker_count: usize = sessions.iter().map(|session| session.markers.len()).sum();
+
+    let mut weekdays: Vec<WeekdayStats> = WEEKDAYS
+        .iter()
+        .map(|&weekday| WeekdayStats {
+            weekday,
+            sessions: 0,
+            recorded_ms: 0,
+        })
+        .collect();
+    let mut hours: Vec<HourStats> = (0..24)
+        .map(|hour| HourStats {
+            hour,
+            sessions: 0,
+            markers: 0,
+        })
+        .collect();
+    let mut days = BTreeSet::new();
+
+    for session in &sessions {
+        let start = local(session.start_time);
+        let weekday = &mut weekdays[start.weekday().num_days_from_monday() as usize];
+        weekday.sessions += 1;
+        weekday.recorded_ms += session.duration().num_milliseconds().max(0);
+        hours[start.hour() as usize].sessions += 1;
+        days.insert(start.date_naive());
+
+        for marker in &session.markers {
+            hours[local(marker.timestamp).hour() as usize].markers += 1;
+        }
+    }
+
+    let busiest_weekday = weekdays
+        .iter()
+        .filter(|weekday| weekday.sessions > 0)
+        .max_by_key(|weekday| (weekday.sessions, weekday.recorded_ms))
+        .map(|weekday| weekday.weekday);
+    let busiest_hour = hours
+        .iter()
+        .filter(|hour| hour.sessions + hour.markers > 0)
+        .max_by_key(|hour| (hour.sessions, hour.markers))
+        .map(|hour| hour.hour);
+
+    let markers = || sessions.iter().flat_map(|session| &session.markers);
+
+    SessionStats {
+        from,
+        to,
+        session_count: sessions.len(),
+        total_recorded_ms,
+        average_length_ms: match sessions.len() {
+            0 => 0,
+            count => total_recorded_ms / count as i64,
+        },
+        marker_count,
+        markers_per_hour: match total_recorded_ms {
+            0 => 0.0,
+            ms => marker_count as f64 / (ms as f64 / 3_600_000.0),
+        },
+        weekdays,
+        hours,
+        busiest_weekday,
+        busiest_hour,
+        labels: frequencies(markers().map(|marker| marker.label.as_str())),
+        categories: frequencies(markers().filter_map(|marker| marker.category.as_deref())),
+        longest_streak: longest_streak(&days),
+    }
+}
+
+/// Writes `stats` as CSV: one section per table, separated by blank lines.
+fn export_csv(stats: &SessionStats, path: &Path) -> Result<(), io::Error> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let date = |date: Option<NaiveDate>| date.map(|date| date.to_string()).unwrap_or_default();
+
+    writeln!(file, "Metric,Value")?;
+    writeln!(file, "From,{}", date(stats.from))?;
+    writeln!(file, "To,{}", date(stats.to))?;
+    writeln!(file, "Sessions,{}", stats.session_count)?;
+    writeln!(file, "Total Recorded (ms),{}", stats.total_recorded_ms)?;
+    writeln!(file, "Average Length (ms),{}", stats.average_length_ms)?;
+    writeln!(file, "Markers,{}", stats.marker_count)?;
+    writeln!(file, "Markers per Hour,{:.2}", stats.markers_per_hour)?;
+    writeln!(
+        file,
+        "Busiest Weekday,{}",
+        stats
+            .busiest_weekday
+            .map(|weekday| weekday.to_string())
+            .unwrap_or_default()
+    )?;
+    writeln!(
+        file,
+        "Busiest Hour,{}",
+        stats
+            .busiest_hour
+            .map(|hour| hour.to_string())
+            .unwrap_or_default()
+    )?;
+    match &stats.longest_streak {
+        Some(streak) => writeln!(
+            file,
+            "Longest Streak (days),{} ({} to {})",
+            streak.days, streak.start, streak.end
+        )?,
+        None => writeln!(file, "Longest Streak (days),0")?,
+    }
+
+    writeln!(file, "\nWeekday,Sessions,Recorded (ms)")?;
+    for weekday in &stats.weekdays {
+        writeln!(
+            file,
+            "{},{},{}",
+            weekday.weekday, weekday.sessions, weekday.recorded_ms
+        )?;
+    }
+
+    writeln!(file, "\nHour,Sessions,Markers")?;
+    for hour in &stats.hours {
+        writeln!(file, "{},{},{}", hour.hour, hour.sessions, hour.markers)?;
+    }
+
+    writeln!(file, "\nLabel,Count")?;
+    for label in &stats.labels {
+        writeln!(file, "{},{}", csv_field(&label.value), label.count)?;
+    }
+
+    writeln!(file, "\nCategory,Count")?;
+    for category in &stats.categories {
+        writeln!(file, "{},{}", csv_field(&category.value), category.count)?;
+    }
+
+    file.flush()
+}
+
+fn export_json(stats: &SessionStats, path: &Path) -> Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(BufWriter::new(file), stats)?;
+    Ok(())
+}
+
+fn project_stats(
+    app_state: &crate::AppState,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<SessionStats, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let sessions = storage.list_sessions().map_err(|e| e.to_string())?;
+    Ok(compute(&sessions, from, to))
+}
+
+// Tauri command functions
+/// Statistics of the sessions that started between `from` and `to`, local
+/// dates, both inclusive and both optional.
+#[tauri::command]
+pub async fn get_session_stats(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<SessionStats, String> {
+    project_stats(&app_state, from, to)
+}
+
+/// Exports the statistics as JSON or CSV to a file picked by the user.
+#[tauri::command]
+pub async fn export_session_stats(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    format: ExportFormat,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<String, String> {
+    let filter_name = match format {
+        ExportFormat::JSON => "JSON",
+        ExportFormat::CSV => "CSV",
+        ExportFormat::Markdown => {
+            return Err("Statistics can only be exported as JSON or CSV".to_string())
+        }
+    };
+
+    let stats = project_stats(&app_state, from, to)?;
+
+    use tauri_plugin_dialog::DialogExt;
+
+    let file_path = handle
+        .dialog()
+        .file()
+        .set_title("Export Statistics")
+        .add_filter(filter_name, &[format.extension()])
+        .blocking_save_file()
+        .ok_or_else(|| "No file selected".to_string())?;
+    let path = file_path
+        .as_path()
+        .ok_or_else(|| "Invalid file path".to_string())?;
+
+    match format {
+        ExportFormat::CSV => export_csv(&stats, path).map_err(|e| e.to_string())?,
+        _ => export_json(&stats, path).map_err(|e| e.to_string())?,
+    }
+
+    Ok("Statistics exported successfully".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Marker;
+    use chrono::{Duration, TimeZone};
+    use serde_json::json;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+    }
+
+    /// An hour long session starting at local noon on `day`, with markers
+    /// labelled `labels`.
+    fn session(day: NaiveDate, labels: &[&str]) -> Session {
+        let start = Local
+            .from_local_datetime(&day.and_hms_opt(12, 0, 0).unwrap())
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let mut session: Session = serde_json::from_value(json!({
+            "id": day.to_string(),
+            "start_time": start,
+            "end_time": start + Duration::hours(1),
+            "markers": [],
+            "name": null,
+            "description": null,
+        }))
+        .unwrap();
+        session.markers = labels
+            .iter()
+            .enumerate()
+            .map(|(index, label)| Marker {
+                id: format!("{}-{}", day, index),
+                timestamp: start + Duration::minutes(10),
+                label: label.to_string(),
+                notes: None,
+                category: None,
+                recording_offset_ms: None,
+                elapsed_ms: None,
+                duration_ms: None,
+            })
+            .collect();
+        session
+    }
+
+    #[test]
+    fn longest_streak_finds_the_longest_run_of_days() {
+        assert!(longest_streak(&BTreeSet::new()).is_none());
+
+        let days: BTreeSet<NaiveDate> = [
+            date(1, 30),
+            date(1, 31),
+            date(2, 1),
+            date(2, 3),
+            date(2, 5),
+            date(2, 6),
+            date(2, 7),
+            date(2, 8),
+        ]
+        .into_iter()
+        .collect();
+        let streak = longest_streak(&days).unwrap();
+        assert_eq!((streak.start, streak.end, streak.days), (date(2, 5), date(2, 8), 4));
+
+        // Across a month, and the earliest of equally long runs
+        let days: BTreeSet<NaiveDate> = [
+            date(1, 30),
+            date(1, 31),
+            date(2, 1),
+            date(2, 10),
+            date(2, 11),
+            date(2, 12),
+        ]
+        .into_iter()
+        .collect();
+        let streak = longest_streak(&days).unwrap();
+        assert_eq!((streak.start, streak.end, streak.days), (date(1, 30), date(2, 1), 3));
+    }
+
+    #[test]
+    fn compute_counts_sessions_within_the_range() {
+        let sessions = [
+            session(date(3, 1), &["Intro"]),
+            session(date(3, 2), &["Intro", "Outro"]),
+            session(date(3, 3), &["Outro"]),
+            session(date(3, 6), &["Intro"]),
+        ];
+
+        let stats = compute(&sessions, Some(date(3, 2)), Some(date(3, 6)));
+        assert_eq!(stats.session_count, 3);
+        assert_eq!(stats.marker_count, 4);
+        assert_eq!(stats.total_recorded_ms, 3 * 3_600_000);
+        assert_eq!(stats.average_length_ms, 3_600_000);
+        assert!((stats.markers_per_hour - 4.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.hours[12].sessions, 3);
+        assert_eq!(stats.hours[12].markers, 4);
+        assert_eq!(stats.busiest_hour, Some(12));
+
+        let streak = stats.longest_streak.unwrap();
+        assert_eq!((streak.start, streak.end, streak.days), (date(3, 2), date(3, 3), 2));
+
+        let labels: Vec<(&str, usize)> = stats
+            .labels
+            .iter()
+            .map(|label| (label.value.as_str(), label.count))
+            .collect();
+        assert_eq!(labels, [("Intro", 2), ("Outro", 2)]);
+
+        let stats = compute(&sessions, None, Some(date(3, 1)));
+        assert_eq!(stats.session_count, 1);
+        assert_eq!(stats.busiest_weekday, Some(Weekday::Sun));
+
+        let stats = compute(&sessions, Some(date(3, 7)), None);
+        assert_eq!(stats.session_count, 0);
+        assert_eq!(stats.markers_per_hour, 0.0);
+        assert!(stats.longest_streak.is_none());
+    }
+}
@@ -0,0 +1,539 @@
+//! Scheduled backups of the session store and restoring from them.
+//!
+//! A backup is a zip archive holding the stored sessions of every project as
+//! `sessions/<project id>/session_<id>.json`, with archived and trashed ones
+//! under `archive/` and `trash/` in the project's folder, next to a
+//! `manifest.json` that lists the projects and each session with its SHA-256
+//! checksum. Restoring checks the whole archive against its manifest before
+//! anything is written.
+
+use crate::{
+    migrations,
+    projects::{self, Project, ProjectList},
+    session::Session,
+    storage::{self, Storage},
+    AppState,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::Duration as StdDuration,
+};
+use tauri::{AppHandle, Manager};
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+const CHECK_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60);
+const MANIFEST_NAME: &str = "manifest.json";
+const FILE_PREFIX: &str = "markermoment-backup-";
+/// Milliseconds keep backups made within a second apart. Names without them,
+/// from older versions, still parse.
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+/// Version of the archive layout, not of the sessions in it. Version 2 added
+/// archived and trashed sessions.
+const FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupFrequency {
+    Daily,
+    Weekly,
+}
+
+impl BackupFrequency {
+    fn interval(&self) -> Duration {
+        match self {
+            BackupFrequency::Daily => Duration::days(1),
+            BackupFrequency::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    /// Folder the archives are written to.
+    pub directory: Option<PathBuf>,
+    pub frequency: BackupFrequency,
+    /// Number of archives kept; older ones are deleted. 0 keeps all of them.
+    pub keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            frequency: BackupFrequency::Daily,
+            keep: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub created: DateTime<Utc>,
+    pub app_version: String,
+    /// Schema version of the sessions when they were backed up.
+    pub schema_version: u64,
+    pub projects: Vec<Project>,
+    pub sessions: Vec<BackupEntry>,
+}
+
+/// Folder of its project a session was backed up from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionFolder {
+    #[default]
+    Sessions,
+    Archive,
+    Trash,
+}
+
+impl SessionFolder {
+    const ALL: [SessionFolder; 3] = [
+        SessionFolder::Sessions,
+        SessionFolder::Archive,
+        SessionFolder::Trash,
+    ];
+
+    fn dir(&self, sessions_dir: &Path) -> PathBuf {
+        match self {
+            SessionFolder::Sessions => sessions_dir.to_path_buf(),
+            SessionFolder::Archive => storage::archive_dir(sessions_dir),
+            SessionFolder::Trash => storage::trash_dir(sessions_dir),
+        }
+    }
+
+    /// Prefix of the session files within the project's folder in the archive.
+    fn archive_prefix(&self) -> &'static str {
+        match self {
+            SessionFolder::Sessions => "",
+            SessionFolder::Archive => "archive/",
+            SessionFolder::Trash => "trash/",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub id: String,
+    pub project_id: String,
+    /// Missing in archives of format version 1, which only held these.
+    #[serde(default)]
+    pub folder: SessionFolder,
+    pub name: Option<String>,
+    pub start_time: DateTime<Utc>,
+    /// Path of the session file in the archive.
+    pub path: String,
+    pub sha256: String,
+}
+
+/// A backup archive, as listed for the user.
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    pub created: DateTime<Utc>,
+    pub manifest: BackupManifest,
+}
+
+fn checksum(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// When the archive at `path` was created, going by its file name.
+fn created_from_name(path: &Path) -> Option<DateTime<Utc>> {
+    let stem = path.file_stem()?.to_str()?.strip_prefix(FILE_PREFIX)?;
+    NaiveDateTime::parse_from_str(stem, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|created| created.and_utc())
+}
+
+/// Archives in `dir` with the time they were created, oldest first.
+fn backup_files(dir: &Path) -> Result<Vec<(PathBuf, DateTime<Utc>)>> {
+    let mut backups = Vec::new();
+
+    if !dir.exists() {
+        return Ok(backups);
+    }
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {:?}", dir))? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("zip") {
+            continue;
+        }
+        if let Some(created) = created_from_name(&path) {
+            backups.push((path, created));
+        }
+    }
+
+    backups.sort_by_key(|(_, created)| *created);
+    Ok(backups)
+}
+
+/// Writes a backup of every project's sessions to `dir`. Returns its path.
+pub fn create_backup(storage: &Storage, projects: &ProjectList, dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create backup directory at {:?}", dir))?;
+
+    let created = Utc::now();
+    let path = dir.join(format!(
+        "{}{}.zip",
+        FILE_PREFIX,
+        created.format(TIMESTAMP_FORMAT)
+    ));
+    let temp_path = path.with_extension("zip.tmp");
+
+    let file = File::create(&temp_path)
+        .with_context(|| format!("Failed to create file at {:?}", temp_path))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut entries = Vec::new();
+    for project in &projects.projects {
+        let sessions_dir = projects::sessions_dir(storage.data_dir(), &project.id);
+        for folder in SessionFolder::ALL {
+            let dir = folder.dir(&sessions_dir);
+            for session in storage::list_sessions_in(&dir)? {
+                let session_path = storage::session_path_in(&dir, &session.id);
+                let bytes = fs::read(&session_path)
+                    .with_context(|| format!("Failed to read {:?}", session_path))?;
+                let archive_path = format!(
+                    "sessions/{}/{}session_{}.json",
+                    project.id,
+                    folder.archive_prefix(),
+                    session.id
+                );
+
+                zip.start_file(archive_path.as_str(), options)?;
+                zip.write_all(&bytes)?;
+
+                entries.push(BackupEntry {
+                    id: session.id,
+                    project_id: project.id.clone(),
+                    folder,
+                    name: session.name,
+                    start_time: session.start_time,
+                    path: archive_path,
+                    sha256: checksum(&bytes),
+                });
+            }
+        }
+    }
+
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        created,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version: migrations::CURRENT_VERSION,
+        projects: projects.projects.clone(),
+        sessions: entries,
+    };
+    zip.start_file(MANIFEST_NAME, options)?;
+    serde_json::to_writer_pretty(&mut zip, &manifest).context("Failed to write backup manifest")?;
+
+    let file = zip.finish().context("Failed to finish backup archive")?;
+    file.sync_all()
+        .with_context(|| format!("Failed to sync {:?}", temp_path))?;
+    fs::rename(&temp_path, &path)
+        .with_context(|| format!("Failed to move {:?} to {:?}", temp_path, path))?;
+
+    Ok(path)
+}
+
+/// Deletes all but the newest `keep` archives in `dir`.
+fn prune(dir: &Path, keep: usize) -> Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    let backups = backup_files(dir)?;
+    let excess = backups.len().saturating_sub(keep);
+    for (path, _) in backups.into_iter().take(excess) {
+        fs::remove_file(&path).with_context(|| format!("Failed to delete {:?}", path))?;
+    }
+
+    Ok(())
+}
+
+fn read_manifest<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>) -> Result<BackupManifest> {
+    let file = archive
+        .by_name(MANIFEST_NAME)
+        .context("The archive has no manifest")?;
+    let manifest: BackupManifest =
+        serde_json::from_reader(file).context("Failed to read the backup manifest")?;
+
+    if manifest.format_version > FORMAT_VERSION {
+        bail!(
+            "The backup has format version {}, but this version of the app only reads up to {}",
+            manifest.format_version,
+            FORMAT_VERSION
+        );
+    }
+
+    // IDs become paths when restoring, so they must not leave the data directory
+    let project_ids = manifest.projects.iter().map(|project| &project.id);
+    for id in project_ids.chain(manifest.sessions.iter().map(|entry| &entry.project_id)) {
+        if !is_project_id(id) {
+            bail!("The backup names an invalid project ID {:?}", id);
+        }
+    }
+    for entry in &manifest.sessions {
+        if entry.id.is_empty() || entry.id.contains(['/', '\\', ':']) || entry.id.contains("..") {
+            bail!("The backup names an invalid session ID {:?}", entry.id);
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Whether `id` is the default project or a plain UUID, as given to new projects.
+fn is_project_id(id: &str) -> bool {
+    id == projects::DEFAULT_PROJECT_ID
+        || Uuid::try_parse(id).is_ok_and(|uuid| uuid.hyphenated().to_string() == id)
+}
+
+/// Reads the manifest of the archive at `path`.
+pub fn backup_info(path: &Path) -> Result<BackupInfo> {
+    let file = File::open(path).with_context(|| format!("Failed to open file at {:?}", path))?;
+    let mut archive =
+        ZipArchive::new(file).with_context(|| format!("{:?} is not a zip archive", path))?;
+    let manifest = read_manifest(&mut archive)?;
+
+    Ok(BackupInfo {
+        path: path.to_path_buf(),
+        created: manifest.created,
+        manifest,
+    })
+}
+
+/// Reads the sessions with `ids`, or all of them, from the archive at `path`,
+/// checking each against the manifest.
+fn read_backup(
+    path: &Path,
+    ids: Option<&[String]>,
+) -> Result<(BackupManifest, Vec<(BackupEntry, Session)>)> {
+    let file = File::open(path).with_context(|| format!("Failed to open file at {:?}", path))?;
+    let mut archive =
+        ZipArchive::new(file).with_context(|| format!("{:?} is not a zip archive", path))?;
+    let manifest = read_manifest(&mut archive)?;
+
+    if let Some(ids) = ids {
+        for id in ids {
+            if !manifest.sessions.iter().any(|entry| &entry.id == id) {
+                bail!("Session with ID {} is not in the backup", id);
+            }
+        }
+    }
+
+    let mut sessions = Vec::new();
+    for entry in &manifest.sessions {
+        if ids.is_some_and(|ids| !ids.contains(&entry.id)) {
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+        archive
+            .by_name(&entry.path)
+            .with_context(|| format!("{} is missing from the archive", entry.path))?
+            .read_to_end(&mut bytes)
+            .with_context(|| format!("Failed to read {} from the archive", entry.path))?;
+
+        if checksum(&bytes) != entry.sha256 {
+            bail!("{} does not match its checksum", entry.path);
+        }
+
+        let document = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", entry.path))?;
+        let (session, _) = storage::session_from_any_document(document)
+            .with_context(|| format!("Invalid session in {}", entry.path))?;
+        if session.id != entry.id {
+            bail!(
+                "{} holds session {} instead of {}",
+                entry.path,
+                session.id,
+                entry.id
+            );
+        }
+
+        sessions.push((entry.clone(), session));
+    }
+
+    Ok((manifest, sessions))
+}
+
+/// Restores the sessions with `ids`, or all of them, from the archive at
+/// `path` into the folders of their projects they were backed up from,
+/// recreating projects that no longer exist.
+/// Sessions that are still stored are overwritten, and restored sessions are
+/// shared if sync is enabled. Nothing is written unless every selected session
+/// in the archive is intact.
+pub fn restore_backup(
+    storage: &mut Storage,
+    projects: &mut ProjectList,
+    path: &Path,
+    ids: Option<&[String]>,
+) -> Result<Vec<Session>> {
+    let (manifest, sessions) = read_backup(path, ids)?;
+
+    let mut projects_changed = false;
+    for (entry, _) in &sessions {
+        if projects.find(&entry.project_id).is_some() {
+            continue;
+        }
+        let project = manifest
+            .projects
+            .iter()
+            .find(|project| project.id == entry.project_id)
+            .with_context(|| format!("Project {} is not in the backup", entry.project_id))?;
+        projects.projects.push(project.clone());
+        projects_changed = true;
+    }
+    if projects_changed {
+        projects.save(storage.data_dir())?;
+    }
+
+    let mut restored = Vec::new();
+    for (entry, session) in sessions {
+        let sessions_dir = projects::sessions_dir(storage.data_dir(), &entry.project_id);
+        match entry.folder {
+            SessionFolder::Sessions => storage.save_session_to(&sessions_dir, &session)?,
+            // Archived and trashed sessions aren't synced
+            folder => {
+                let dir = folder.dir(&sessions_dir);
+                fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create directory at {:?}", dir))?;
+                storage::save_session_in(&dir, &session)?;
+            }
+        }
+        restored.push(session);
+    }
+
+    Ok(restored)
+}
+
+fn backup_dir(app_state: &AppState) -> Result<PathBuf> {
+    let settings = app_state.settings.lock().unwrap();
+    match &settings.backups.directory {
+        Some(dir) => Ok(dir.clone()),
+        None => bail!("No backup directory is configured"),
+    }
+}
+
+/// Backs up now and deletes archives beyond the number to keep.
+pub fn run_backup(app_handle: &AppHandle) -> Result<PathBuf> {
+    // Encrypted sessions can't be listed until unlocked
+    if crate::encryption::is_locked() {
+        bail!("Unlock the session store before backing it up");
+    }
+
+    let state = app_handle.state::<AppState>();
+    let dir = backup_dir(&state)?;
+    let keep = state.settings.lock().unwrap().backups.keep;
+
+    let path = {
+        let storage = state.storage.lock().unwrap();
+        let projects = state.projects.lock().unwrap();
+        create_backup(&storage, &projects, &dir)?
+    };
+    log::info!("Backed up sessions to {:?}", path);
+
+    prune(&dir, keep)?;
+    Ok(path)
+}
+
+/// Backs up if backups are enabled and the latest one is older than the
+/// configured frequency.
+fn run_if_due(app_handle: &AppHandle) -> Result<()> {
+    let settings = app_handle
+        .state::<AppState>()
+        .settings
+        .lock()
+        .unwrap()
+        .backups
+        .clone();
+    let Some(dir) = settings.directory.filter(|_| settings.enabled) else {
+        return Ok(());
+    };
+    // Tried again once unlocked
+    if crate::encryption::is_locked() {
+        return Ok(());
+    }
+
+    let latest = backup_files(&dir)?.last().map(|(_, created)| *created);
+    if latest.is_none_or(|latest| Utc::now() - latest >= settings.frequency.interval()) {
+        run_backup(app_handle)?;
+    }
+
+    Ok(())
+}
+
+/// Checks every hour whether a backup is due.
+pub fn spawn_scheduler(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = run_if_due(&app_handle) {
+                log::error!("Scheduled backup failed: {:?}", err);
+            }
+        }
+    });
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn backup_now(handle: tauri::AppHandle) -> Result<PathBuf, String> {
+    run_backup(&handle).map_err(|e| e.to_string())
+}
+
+/// Backups in the configured directory, newest first. Archives that can't be
+/// read are left out.
+#[tauri::command]
+pub async fn list_backups(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<BackupInfo>, String> {
+    let dir = backup_dir(&app_state).map_err(|e| e.to_string())?;
+
+    let mut backups = Vec::new();
+    for (path, _) in backup_files(&dir)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .rev()
+    {
+        match backup_info(&path) {
+            Ok(info) => backups.push(info),
+            Err(err) => log::warn!("Skipping unreadable backup {:?}: {:?}", path, err),
+        }
+    }
+
+    Ok(backups)
+}
+
+/// Restores the sessions with `ids`, or all of them, from the archive at `path`.
+#[tauri::command]
+pub async fn restore_backup_archive(
+    path: PathBuf,
+    ids: Option<Vec<String>>,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<Session>, String> {
+    let restored = {
+        let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+        let mut projects = app_state.projects.lock().map_err(|e| e.to_string())?;
+        restore_backup(&mut storage, &mut projects, &path, ids.as_deref())
+            .map_err(|e| format!("{:#}", e))?
+    };
+
+    // Projects may have been recreated
+    crate::tray::refresh_tray_menu(&handle);
+
+    Ok(restored)
+}
@@ -0,0 +1,530 @@
+//! Cut lists for trimming clips around markers out of the recording.
+//!
+//! Each marker becomes a cut from its media time minus the pre-roll to its
+//! end plus the post-roll. Range markers keep their full length. Cuts that
+//! overlap or touch are merged. The list can be written as a shell script
+//! that runs ffmpeg, as a CSV, or as a CMX 3600 EDL for editors.
+
+use crate::{
+    session::{self, Session},
+    storage,
+    timecode::{self, FrameRate, Timecode},
+};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Where the record side of an EDL starts, as is usual for programme timelines.
+const EDL_RECORD_START_HOURS: i64 = 1;
+
+/// Longest pre- or post-roll. Nobody needs more than a day around a marker,
+/// and the limit keeps the cut times from overflowing.
+const MAX_ROLL_MS: u64 = 24 * 60 * 60 * 1_000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CutListOptions {
+    /// Time kept before each marker.
+    pub pre_roll_ms: u64,
+    /// Time kept after each marker, or after the end of a range marker.
+    pub post_roll_ms: u64,
+    /// Only markers of this category, e.g. `Highlight`.
+    pub category: Option<String>,
+    /// Only these markers.
+    pub marker_ids: Option<Vec<String>>,
+}
+
+impl Default for CutListOptions {
+    fn default() -> Self {
+        Self {
+            pre_roll_ms: 5_000,
+            post_roll_ms: 10_000,
+            category: None,
+            marker_ids: None,
+        }
+    }
+}
+
+/// A stretch of the recording to keep. Times are media times.
+#[derive(Debug, Clone, Serialize)]
+pub struct Cut {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub marker_ids: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+impl Cut {
+    fn start(&self) -> Duration {
+        Duration::milliseconds(self.start_ms)
+    }
+
+    fn end(&self) -> Duration {
+        Duration::milliseconds(self.end_ms)
+    }
+
+    /// Marker labels on one line, for comments.
+    fn description(&self) -> String {
+        self.labels.join("; ").replace(['\r', '\n'], " ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CutList {
+    /// The recording to cut, if the session has one.
+    pub recording: Option<PathBuf>,
+    /// In order of media time.
+    pub cuts: Vec<Cut>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CutListFormat {
+    /// A shell script running ffmpeg once per cut.
+    Ffmpeg,
+    CSV,
+    EDL,
+}
+
+impl CutListFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CutListFormat::Ffmpeg => "sh",
+            CutListFormat::CSV => "csv",
+            CutListFormat::EDL => "edl",
+        }
+    }
+}
+
+/// The OBS recording of `session`, otherwise its first media file.
+fn recording(session: &Session) -> Option<PathBuf> {
+    session
+        .recording_path
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| session.media.first().map(|media| media.path.clone()))
+}
+
+pub fn build(session: &Session, options: &CutListOptions) -> Result<CutList, String> {
+    if options.pre_roll_ms > MAX_ROLL_MS || options.post_roll_ms > MAX_ROLL_MS {
+        return Err(format!(
+            "The pre- and post-roll can be at most {} ms",
+            MAX_ROLL_MS
+        ));
+    }
+
+    let pre_roll = Duration::milliseconds(options.pre_roll_ms as i64);
+    let post_roll = Duration::milliseconds(options.post_roll_ms as i64);
+
+    let mut cuts: Vec<Cut> = session
+        .markers
+        .iter()
+        .filter(|marker| {
+            options
+                .category
+                .as_ref()
+                .is_none_or(|category| marker.category.as_ref() == Some(category))
+                && options
+                    .marker_ids
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&marker.id))
+        })
+        .filter_map(|marker| {
+            let start = (session.media_time(marker) - pre_roll).max(Duration::zero());
+            let end = session.media_end_time(marker) + post_roll;
+
+            // Entirely before the recording started
+            if end <= start {
+                return None;
+            }
+
+            Some(Cut {
+                start_ms: start.num_milliseconds(),
+                end_ms: end.num_milliseconds(),
+                marker_ids: vec![marker.id.clone()],
+                labels: vec![marker.label.clone()],
+            })
+        })
+        .collect();
+    cuts.sort_by_key(|cut| cut.start_ms);
+
+    let mut merged: Vec<Cut> = Vec::new();
+    for cut in cuts {
+        match merged.last_mut() {
+            Some(previous) if cut.start_ms <= previous.end_ms => {
+                previous.end_ms = previous.end_ms.max(cut.end_ms);
+                previous.marker_ids.extend(cut.marker_ids);
+                previous.labels.extend(cut.labels);
+            }
+            _ => merged.push(cut),
+        }
+    }
+
+    Ok(CutList {
+        recording: recording(session),
+        cuts: merged,
+    })
+}
+
+/// Quotes `value` for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn seconds(time: Duration) -> String {
+    format!("{:.3}", time.num_milliseconds() as f64 / 1000.0)
+}
+
+/// Writes a script that cuts each clip out of the recording with ffmpeg,
+/// without re-encoding. The recording can be passed as the first argument.
+fn export_ffmpeg(session: &Session, cut_list: &CutList, path: &Path) -> Result<(), io::Error> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    writeln!(file, "#!/bin/sh")?;
+    writeln!(
+        file,
+        "# Cut list for {}, generated by MarkerMoment.",
+        session
+            .name
+            .as_deref()
+            .unwrap_or(&session.id)
+            .replace(['\r', '\n'], " ")
+    )?;
+    writeln!(
+        file,
+        "# Usage: sh {} [RECORDING]",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    )?;
+    writeln!(
+        file,
+        "# Stream copy cuts at the nearest keyframes; drop -c copy to cut exactly."
+    )?;
+    writeln!(file, "set -e")?;
+    writeln!(file)?;
+
+    match &cut_list.recording {
+        Some(recording) => writeln!(
+            file,
+            "INPUT=${{1:-{}}}",
+            shell_quote(&recording.to_string_lossy())
+        )?,
+        None => writeln!(file, "INPUT=${{1:?Usage: $0 RECORDING}}")?,
+    }
+    writeln!(file, "BASE=${{INPUT%.*}}")?;
+    writeln!(file, "EXT=${{INPUT##*.}}")?;
+
+    for (index, cut) in cut_list.cuts.iter().enumerate() {
+        writeln!(file)?;
+        writeln!(file, "# {}", cut.description())?;
+        writeln!(
+            file,
+            "ffmpeg -hide_banner -y -ss {} -i \"$INPUT\" -t {} -c copy \"${{BASE}}_clip_{:02}.${{EXT}}\"",
+            seconds(cut.start()),
+            seconds(cut.end() - cut.start()),
+            index + 1
+        )?;
+    }
+
+    file.flush()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
+}
+
+fn export_csv(
+    session: &Session,
+    cut_list: &CutList,
+    path: &Path,
+    default_rate: FrameRate,
+) -> Result<(), io::Error> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    writeln!(
+        file,
+        "Cut,Start,End,Duration,Start Timecode,End Timecode,Markers"
+    )?;
+
+    for (index, cut) in cut_list.cuts.iter().enumerate() {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{}",
+            index + 1,
+            storage::format_media_time(cut.start()),
+            storage::format_media_time(cut.end()),
+            storage::format_media_time(cut.end() - cut.start()),
+            timecode::media_timecode(session, cut.start(), default_rate),
+            timecode::media_timecode(session, cut.end(), default_rate),
+            storage::csv_field(&cut.description())
+        )?;
+    }
+
+    file.flush()
+}
+
+/// Writes a CMX 3600 EDL placing the cuts one after another on the record side.
+fn export_edl(
+    session: &Session,
+    cut_list: &CutList,
+    path: &Path,
+    default_rate: FrameRate,
+) -> Result<(), io::Error> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let rate = timecode::session_rate(session, default_rate);
+
+    writeln!(
+        file,
+        "TITLE: {}",
+        session
+            .name
+            .as_deref()
+            .unwrap_or(&session.id)
+            .replace(['\r', '\n'], " ")
+    )?;
+    writeln!(
+        file,
+        "FCM: {}",
+        if rate.is_drop_frame() {
+            "DROP FRAME"
+        } else {
+            "NON-DROP FRAME"
+        }
+    )?;
+
+    let clip_name = cut_list
+        .recording
+        .as_ref()
+        .and_then(|recording| recording.file_name())
+        .map(|name| name.to_string_lossy().into_owned());
+
+    let mut record_frame = Timecode {
+        hours: EDL_RECORD_START_HOURS,
+        minutes: 0,
+        seconds: 0,
+        frames: 0,
+        rate,
+    }
+    .to_frames();
+
+    for (index, cut) in cut_list.cuts.iter().enumerate() {
+        let source_in = timecode::media_timecode(session, cut.start(), default_rate);
+        let length = rate.frames_in(cut.end()) - rate.frames_in(cut.start());
+        if length <= 0 {
+            continue;
+        }
+        let source_out = Timecode::from_frames(source_in.to_frames() + length, rate);
+
+        writeln!(file)?;
+        writeln!(
+            file,
+            "{:03}  {:<8} {:<5} C        {} {} {} {}",
+            index + 1,
+            "AX",
+            "AA/V",
+            source_in,
+            source_out,
+            Timecode::from_frames(record_frame, rate),
+            Timecode::from_frames(record_frame + length, rate)
+        )?;
+        if let Some(clip_name) = &clip_name {
+            writeln!(file, "* FROM CLIP NAME: {}", clip_name)?;
+        }
+        writeln!(file, "* COMMENT: {}", cut.description())?;
+
+        record_frame += length;
+    }
+
+    file.flush()
+}
+
+/// Writes `cut_list` to `path` in `format`.
+pub fn export_to(
+    session: &Session,
+    cut_list: &CutList,
+    path: &Path,
+    format: CutListFormat,
+    default_rate: FrameRate,
+) -> Result<(), io::Error> {
+    match format {
+        CutListFormat::Ffmpeg => export_ffmpeg(session, cut_list, path),
+        CutListFormat::CSV => export_csv(session, cut_list, path, default_rate),
+        CutListFormat::EDL => export_edl(session, cut_list, path, default_rate),
+    }
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn get_cut_list(
+    session_id: String,
+    options: Option<CutListOptions>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<CutList, String> {
+    let session = session::find_session(&app_state, &session_id)?;
+    build(&session, &options.unwrap_or_default())
+}
+
+/// Exports the cut list of a session to a file picked by the user.
+#[tauri::command]
+pub async fn export_cut_list(
+    session_id: String,
+    options: Option<CutListOptions>,
+    format: CutListFormat,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<String, String> {
+    let default_rate = app_state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .default_frame_rate;
+    let session = session::find_session(&app_state, &session_id)?;
+
+    let cut_list = build(&session, &options.unwrap_or_default())?;
+    if cut_list.cuts.is_empty() {
+        return Err("No markers to cut around".to_string());
+    }
+
+    use tauri_plugin_dialog::DialogExt;
+
+    let filter_name = match format {
+        CutListFormat::Ffmpeg => "Shell Script",
+        CutListFormat::CSV => "CSV",
+        CutListFormat::EDL => "EDL",
+    };
+
+    let file_path = handle
+        .dialog()
+        .file()
+        .set_title("Export Cut List")
+        .add_filter(filter_name, &[format.extension()])
+        .blocking_save_file()
+        .ok_or_else(|| "No file selected".to_string())?;
+    let path = file_path
+        .as_path()
+        .ok_or_else(|| "Invalid file path".to_string())?;
+
+    export_to(&session, &cut_list, path, format, default_rate).map_err(|e| e.to_string())?;
+
+    Ok("Cut list exported successfully".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Marker;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    /// A marker `seconds` into the session.
+    fn marker(id: &str, seconds: u64) -> Marker {
+        Marker {
+            id: id.to_string(),
+            timestamp: "2026-01-01T10:00:00Z".parse().unwrap(),
+            label: id.to_uppercase(),
+            notes: None,
+            category: None,
+            recording_offset_ms: None,
+            elapsed_ms: Some(seconds * 1_000),
+            duration_ms: None,
+        }
+    }
+
+    fn session(markers: Vec<Marker>) -> Session {
+        let mut session: Session = serde_json::from_value(json!({
+            "id": "session",
+            "start_time": "2026-01-01T10:00:00Z",
+            "end_time": "2026-01-01T11:00:00Z",
+            "markers": [],
+            "name": "Episode 1",
+            "description": null,
+        }))
+        .unwrap();
+        session.markers = markers;
+        session
+    }
+
+    fn options(pre_roll_ms: u64, post_roll_ms: u64) -> CutListOptions {
+        CutListOptions {
+            pre_roll_ms,
+            post_roll_ms,
+            ..CutListOptions::default()
+        }
+    }
+
+    #[test]
+    fn overlapping_and_touching_cuts_are_merged() {
+        let mut range = marker("d", 60);
+        range.duration_ms = Some(20_000);
+        let session = session(vec![
+            marker("b", 20),
+            marker("a", 10),
+            marker("c", 35),
+            range,
+        ]);
+
+        let cut_list = build(&session, &options(5_000, 10_000)).unwrap();
+        let cuts: Vec<(i64, i64, Vec<String>)> = cut_list
+            .cuts
+            .into_iter()
+            .map(|cut| (cut.start_ms, cut.end_ms, cut.marker_ids))
+            .collect();
+
+        assert_eq!(
+            cuts,
+            [
+                (5_000, 45_000, vec!["a".into(), "b".into(), "c".into()]),
+                (55_000, 90_000, vec!["d".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn cuts_start_no_earlier_than_the_recording() {
+        let session = session(vec![marker("a", 2)]);
+
+        let cut_list = build(&session, &options(5_000, 1_000)).unwrap();
+
+        assert_eq!(cut_list.cuts[0].start_ms, 0);
+        assert_eq!(cut_list.cuts[0].end_ms, 3_000);
+    }
+
+    #[test]
+    fn oversized_rolls_are_refused() {
+        let session = session(vec![marker("a", 10)]);
+
+        assert!(build(&session, &options(u64::MAX, 0)).is_err());
+        assert!(build(&session, &options(0, u64::MAX)).is_err());
+        assert!(build(&session, &options(MAX_ROLL_MS, MAX_ROLL_MS)).is_ok());
+    }
+
+    #[test]
+    fn edl_places_cuts_back_to_back_in_drop_frame() {
+        let mut session = session(vec![marker("a", 0), marker("b", 120)]);
+        session.frame_rate = Some(FrameRate::Fps29_97Df);
+        let mut cut_list = build(&session, &options(0, 10_000)).unwrap();
+        // Long enough to cross a minute, where frame numbers are dropped
+        cut_list.cuts[0].end_ms = 65_000;
+
+        let path = std::env::temp_dir().join(format!("markermoment-{}.edl", Uuid::new_v4()));
+        export_to(&session, &cut_list, &path, CutListFormat::EDL, FrameRate::Fps30).unwrap();
+        let edl = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(edl.contains("FCM: DROP FRAME"));
+        let events: Vec<&str> = edl.lines().filter(|line| line.contains(" C ")).collect();
+        assert_eq!(
+            events,
+            [
+                "001  AX       AA/V  C        00:00:00;00 00:01:05;00 01:00:00;00 01:01:05;00",
+                "002  AX       AA/V  C        00:01:59;28 00:02:10;00 01:01:05;00 01:01:15;00",
+            ]
+        );
+    }
+}
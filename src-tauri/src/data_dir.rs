@@ -0,0 +1,309 @@
+//! Where the app keeps its data.
+//!
+//! The data directory is, in order of precedence, the one given with
+//! `--data-dir`, the `MARKERMOMENT_DATA_DIR` environment variable, the one
+//! chosen in the app, or `<platform data dir>/com.markermoment.app`. The
+//! choice made in the app can't live in `settings.json`, which is itself in the
+//! data directory, so it is kept in `data-dir.json` in the config directory.
+//!
+//! Older versions always stored their data in `<platform data dir>/dibikaandaagozi`.
+//! That folder is moved to the default location the first time it is used.
+
+use crate::{storage, AppState};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    ffi::OsString,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+use tauri::{AppHandle, Manager};
+
+const APP_IDENTIFIER: &str = "com.markermoment.app";
+const LEGACY_DIR_NAME: &str = "dibikaandaagozi";
+const ENV_VAR: &str = "MARKERMOMENT_DATA_DIR";
+const CLI_FLAG: &str = "--data-dir";
+
+/// Where the data directory in use came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDirSource {
+    CommandLine,
+    Environment,
+    Settings,
+    Default,
+}
+
+/// The data directory chosen in the app, kept outside of it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct DataDirConfig {
+    data_dir: Option<PathBuf>,
+}
+
+impl DataDirConfig {
+    fn path() -> Result<PathBuf> {
+        let config_dir = dirs::config_dir().context("Could not determine the config directory")?;
+        Ok(config_dir.join(APP_IDENTIFIER).join("data-dir.json"))
+    }
+
+    fn load() -> Result<Self> {
+        let file_path = Self::path()?;
+
+        if !file_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let file = File::open(&file_path)
+            .with_context(|| format!("Failed to open file at {:?}", file_path))?;
+
+        let reader = BufReader::new(file);
+        serde_json::from_reader(reader).with_context(|| {
+            format!(
+                "Failed to deserialize data directory config from {:?}",
+                file_path
+            )
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        let file_path = Self::path()?;
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create config directory at {:?}", parent))?;
+        }
+        storage::write_json_atomic(&file_path, self)
+    }
+}
+
+/// `<platform data dir>/com.markermoment.app`.
+fn default_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().context("Could not determine the app data directory")?;
+    Ok(data_dir.join(APP_IDENTIFIER))
+}
+
+fn legacy_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|data_dir| data_dir.join(LEGACY_DIR_NAME))
+}
+
+/// The value of `--data-dir <path>` or `--data-dir=<path>` in `args`.
+fn from_args(args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == CLI_FLAG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(value) = arg
+            .to_str()
+            .and_then(|arg| arg.strip_prefix(CLI_FLAG))
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(PathBuf::from(value));
+        }
+    }
+    None
+}
+
+/// The data directory to use and where it came from.
+fn resolve() -> Result<(PathBuf, DataDirSource)> {
+    if let Some(dir) = from_args(env::args_os().skip(1)) {
+        return Ok((dir, DataDirSource::CommandLine));
+    }
+
+    if let Some(dir) = env::var_os(ENV_VAR).filter(|value| !value.is_empty()) {
+        return Ok((PathBuf::from(dir), DataDirSource::Environment));
+    }
+
+    match DataDirConfig::load() {
+        Ok(DataDirConfig {
+            data_dir: Some(dir),
+        }) => return Ok((dir, DataDirSource::Settings)),
+        Ok(_) => {}
+        Err(err) => log::error!(
+            "Failed to load the configured data directory, using the default: {:?}",
+            err
+        ),
+    }
+
+    Ok((default_dir()?, DataDirSource::Default))
+}
+
+fn is_empty_dir(dir: &Path) -> Result<bool> {
+    if !dir.exists() {
+        return Ok(true);
+    }
+    let mut entries =
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory {:?}", dir))?;
+    Ok(entries.next().is_none())
+}
+
+/// Copies the contents of `from` into `to`, recursively.
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("Failed to create directory at {:?}", to))?;
+
+    for entry in
+        fs::read_dir(from).with_context(|| format!("Failed to read directory {:?}", from))?
+    {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("Failed to copy {:?} to {:?}", entry.path(), target))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the data of older versions to `dir`, unless `dir` already has data.
+fn migrate_legacy(dir: &Path) -> Result<()> {
+    let Some(legacy) = legacy_dir() else {
+        return Ok(());
+    };
+    if !legacy.is_dir() || !is_empty_dir(dir)? {
+        return Ok(());
+    }
+
+    log::info!("Moving data from {:?} to {:?}", legacy, dir);
+
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory at {:?}", parent))?;
+    }
+    // An empty target left by an earlier run would make the rename fail
+    if dir.exists() {
+        fs::remove_dir(dir)
+            .with_context(|| format!("Failed to remove empty directory {:?}", dir))?;
+    }
+
+    if fs::rename(&legacy, dir).is_err() {
+        // Most likely on another file system; copy and leave the old folder be
+        copy_dir(&legacy, dir)?;
+        log::warn!(
+            "Copied data from {:?} to {:?}; the old folder can be deleted",
+            legacy,
+            dir
+        );
+    }
+
+    Ok(())
+}
+
+/// Resolves the data directory, moves the data of older versions into it if it
+/// is the default, and creates it.
+pub fn prepare() -> Result<(PathBuf, DataDirSource)> {
+    let (dir, source) = resolve()?;
+
+    if source == DataDirSource::Default {
+        if let Err(err) = migrate_legacy(&dir) {
+            log::error!("Failed to move data from the legacy folder: {:?}", err);
+        }
+    }
+
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create data directory at {:?}", dir))?;
+
+    Ok((dir, source))
+}
+
+/// Shows why the app couldn't start and quits.
+pub fn report_startup_error(err: anyhow::Error) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+    log::error!("Failed to start: {:?}", err);
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .setup(move |app| {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.hide();
+            }
+
+            let app_handle = app.handle().clone();
+            app.dialog()
+                .message(format!(
+                    "Marker Moment could not set up its data directory:\n\n{:#}\n\nSet another one with {} or the {} environment variable.",
+                    err, CLI_FLAG, ENV_VAR
+                ))
+                .title("Marker Moment")
+                .kind(MessageDialogKind::Error)
+                .show(move |_| app_handle.exit(1));
+
+            Ok(())
+        })
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataDirInfo {
+    pub path: PathBuf,
+    pub source: DataDirSource,
+    /// Directory chosen in the app, used from the next start unless overridden.
+    pub configured: Option<PathBuf>,
+    pub default: Option<PathBuf>,
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn get_data_dir(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<DataDirInfo, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let configured = DataDirConfig::load().map_err(|e| e.to_string())?.data_dir;
+
+    Ok(DataDirInfo {
+        path: storage.data_dir().to_path_buf(),
+        source: resolve().map_err(|e| e.to_string())?.1,
+        configured,
+        default: default_dir().ok(),
+    })
+}
+
+/// Chooses the data directory, or goes back to the default with `None`, and
+/// restarts the app to use it. With `copy_data`, the current data is copied to
+/// the new directory first, which has to be empty.
+#[tauri::command]
+pub async fn set_data_dir(
+    path: Option<PathBuf>,
+    copy_data: bool,
+    handle: AppHandle,
+) -> Result<(), String> {
+    let app_state = handle.state::<AppState>();
+
+    {
+        let active_session = app_state.active_session.lock().map_err(|e| e.to_string())?;
+        if active_session.is_some() {
+            return Err("Stop the current session before changing the data directory".to_string());
+        }
+
+        let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+        let target = match &path {
+            Some(path) => path.clone(),
+            None => default_dir().map_err(|e| e.to_string())?,
+        };
+
+        if copy_data && target != storage.data_dir() {
+            let copy = || -> Result<()> {
+                if target.starts_with(storage.data_dir()) {
+                    bail!("The new data directory can't be inside the current one");
+                }
+                if !is_empty_dir(&target)? {
+                    bail!("{:?} is not empty", target);
+                }
+                copy_dir(storage.data_dir(), &target)
+            };
+            copy().map_err(|e| e.to_string())?;
+        }
+
+        DataDirConfig { data_dir: path }
+            .save()
+            .map_err(|e| e.to_string())?;
+    }
+
+    handle.restart()
+}
@@ -0,0 +1,129 @@
+//! Notices session files changed outside the app, e.g. by the CLI, a sync
+//! tool or by hand.
+//!
+//! The sessions folder of the active project is polled like the recordings
+//! folder. Whenever session files were added, changed or removed since the
+//! last poll, a `sessions-changed` event lists their IDs so the UI can reload
+//! them. Sessions are always read from disk, so there is nothing else to
+//! refresh. The app's own saves are reported too; reloading is harmless.
+
+use crate::{storage, AppState};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tauri::{AppHandle, Emitter as _, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Modification time and size of each session file, by session ID.
+type Snapshot = HashMap<String, (Option<SystemTime>, u64)>;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SessionsChanged {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl SessionsChanged {
+    fn between(previous: &Snapshot, current: &Snapshot) -> Self {
+        let mut changes = Self::default();
+
+        for (id, state) in current {
+            match previous.get(id) {
+                None => changes.added.push(id.clone()),
+                Some(previous) if previous != state => changes.changed.push(id.clone()),
+                Some(_) => {}
+            }
+        }
+        for id in previous.keys() {
+            if !current.contains_key(id) {
+                changes.removed.push(id.clone());
+            }
+        }
+
+        changes
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+fn session_id(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.strip_prefix("session_").map(str::to_string)
+}
+
+fn scan(dir: &Path) -> Result<Snapshot> {
+    let mut snapshot = HashMap::new();
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {:?}", dir))? {
+        let path = entry?.path();
+        if !storage::is_session_file(&path) {
+            continue;
+        }
+        let Some(id) = session_id(&path) else {
+            continue;
+        };
+
+        // Removed between listing and reading; picked up next time
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        snapshot.insert(id, (metadata.modified().ok(), metadata.len()));
+    }
+
+    Ok(snapshot)
+}
+
+fn sessions_dir(app_handle: &AppHandle) -> PathBuf {
+    let state = app_handle.state::<AppState>();
+    let storage = state.storage.lock().unwrap();
+    storage.sessions_dir().to_path_buf()
+}
+
+/// Polls the sessions folder for changes until the app exits.
+pub fn spawn_watcher(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut dir = sessions_dir(&app_handle);
+        let mut snapshot = scan(&dir).unwrap_or_default();
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            poll.tick().await;
+
+            let current_dir = sessions_dir(&app_handle);
+            let current = match scan(&current_dir) {
+                Ok(current) => current,
+                Err(err) => {
+                    log::warn!("Failed to scan sessions folder: {:?}", err);
+                    continue;
+                }
+            };
+
+            // Switching projects is announced with its own event
+            if current_dir != dir {
+                dir = current_dir;
+                snapshot = current;
+                continue;
+            }
+
+            let changes = SessionsChanged::between(&snapshot, &current);
+            snapshot = current;
+
+            if !changes.is_empty() {
+                app_handle
+                    .emit("sessions-changed", &changes)
+                    .unwrap_or_else(|e| {
+                        log::error!("Failed to emit sessions-changed event: {:?}", e)
+                    });
+            }
+        }
+    });
+}
@@ -0,0 +1,716 @@
+//! Optional passphrase-based encryption of stored sessions.
+//!
+//! Sessions are encrypted with XChaCha20-Poly1305 under a random data key.
+//! The data key is itself encrypted ("wrapped") with a key derived from the
+//! passphrase with Argon2id, and kept in `encryption.json` in the data
+//! directory. Changing the passphrase only rewraps the data key; rotating the
+//! key adds a new data key and re-encrypts every session, keeping the old keys
+//! so that older backups can still be restored.
+//!
+//! An encrypted session file is JSON too, `{"encrypted": {...}}`, so atomic
+//! saves and backups handle it like any other. The ciphertext is bound to its
+//! key and session ID. Everything goes through `seal` and `open` in `storage`,
+//! so the rest of the app never sees it. Until the store is unlocked with the
+//! passphrase, no session can be read or saved, and once unlocked, unencrypted
+//! session files are refused. Settings, projects, backup manifests, exports
+//! and the webhook queue and delivery log are not encrypted, and the queue
+//! holds pending event payloads with session names and marker notes. Sync
+//! shares sessions unencrypted, so it can't be used with an encrypted store.
+
+use crate::storage;
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tauri::{AppHandle, Emitter as _, Manager};
+use uuid::Uuid;
+
+const ENVELOPE_FIELD: &str = "encrypted";
+const SALT_LEN: usize = 16;
+
+/// Argon2id parameters for deriving the key from the passphrase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfParams {
+    /// Memory in KiB.
+    memory: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: String,
+}
+
+impl KdfParams {
+    fn generate() -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        Self {
+            memory: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+            salt: STANDARD.encode(salt),
+        }
+    }
+
+    fn derive(&self, passphrase: &str) -> Result<Key> {
+        let params = Params::new(self.memory, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| anyhow!("Invalid key derivation parameters: {}", e))?;
+        let salt = STANDARD
+            .decode(&self.salt)
+            .context("Invalid key derivation salt")?;
+
+        let mut key = Key::default();
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut_slice())
+            .map_err(|e| anyhow!("Failed to derive key from passphrase: {}", e))?;
+        Ok(key)
+    }
+}
+
+/// A data key, encrypted with the key derived from the passphrase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WrappedKey {
+    id: String,
+    nonce: String,
+    key: String,
+}
+
+/// `encryption.json`: how to get from the passphrase to the data keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptionConfig {
+    kdf: KdfParams,
+    /// Key new sessions are encrypted with.
+    active_key: String,
+    /// Every data key used so far, the active one included.
+    keys: Vec<WrappedKey>,
+}
+
+impl EncryptionConfig {
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join("encryption.json")
+    }
+
+    fn load(data_dir: &Path) -> Result<Option<Self>> {
+        let file_path = Self::path(data_dir);
+
+        if !file_path.exists() {
+            return Ok(None);
+        }
+
+        let file = File::open(&file_path)
+            .with_context(|| format!("Failed to open file at {:?}", file_path))?;
+
+        let reader = BufReader::new(file);
+        let config = serde_json::from_reader(reader).with_context(|| {
+            format!(
+                "Failed to deserialize encryption config from {:?}",
+                file_path
+            )
+        })?;
+
+        Ok(Some(config))
+    }
+
+    /// Saves the config without keeping a backup, which would still hold the
+    /// data keys wrapped with the previous passphrase.
+    fn save(&self, data_dir: &Path) -> Result<()> {
+        let path = Self::path(data_dir);
+        storage::write_json_atomic(&path, self)?;
+        remove_backup(&path)
+    }
+
+    /// Unwraps every data key with the key derived from `passphrase`.
+    fn unlock(&self, passphrase: &str) -> Result<Keyring> {
+        let wrapping = XChaCha20Poly1305::new(&self.kdf.derive(passphrase)?);
+
+        let mut keys = Vec::new();
+        for wrapped in &self.keys {
+            let nonce = decode_nonce(&wrapped.nonce)?;
+            let ciphertext = STANDARD
+                .decode(&wrapped.key)
+                .context("Invalid wrapped key")?;
+            let key = wrapping
+                .decrypt(
+                    &nonce,
+                    Payload {
+                        msg: &ciphertext,
+                        aad: wrapped.id.as_bytes(),
+                    },
+                )
+                .map_err(|_| anyhow!("Wrong passphrase"))?;
+            keys.push((wrapped.id.clone(), *Key::from_slice(&key)));
+        }
+
+        Ok(Keyring {
+            active: self.active_key.clone(),
+            keys,
+        })
+    }
+
+    /// A config for `keyring` under `passphrase`, with a fresh salt.
+    fn wrap(keyring: &Keyring, passphrase: &str) -> Result<Self> {
+        let kdf = KdfParams::generate();
+        let wrapping = XChaCha20Poly1305::new(&kdf.derive(passphrase)?);
+
+        let mut keys = Vec::new();
+        for (id, key) in &keyring.keys {
+            let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let wrapped = wrapping
+                .encrypt(
+                    &nonce,
+                    Payload {
+                        msg: key.as_slice(),
+                        aad: id.as_bytes(),
+                    },
+                )
+                .map_err(|_| anyhow!("Failed to wrap data key"))?;
+            keys.push(WrappedKey {
+                id: id.clone(),
+                nonce: STANDARD.encode(nonce),
+                key: STANDARD.encode(wrapped),
+            });
+        }
+
+        Ok(Self {
+            kdf,
+            active_key: keyring.active.clone(),
+            keys,
+        })
+    }
+}
+
+fn remove_backup(path: &Path) -> Result<()> {
+    let backup = storage::backup_path(path);
+    if backup.exists() {
+        fs::remove_file(&backup).with_context(|| format!("Failed to delete {:?}", backup))?;
+    }
+    Ok(())
+}
+
+fn decode_nonce(encoded: &str) -> Result<XNonce> {
+    let nonce = STANDARD.decode(encoded).context("Invalid nonce")?;
+    if nonce.len() != 24 {
+        bail!("Invalid nonce length {}", nonce.len());
+    }
+    Ok(*XNonce::from_slice(&nonce))
+}
+
+/// The unwrapped data keys.
+#[derive(Clone)]
+struct Keyring {
+    active: String,
+    keys: Vec<(String, Key)>,
+}
+
+impl Keyring {
+    fn generate() -> Self {
+        let mut keyring = Self {
+            active: String::new(),
+            keys: Vec::new(),
+        };
+        keyring.add_key();
+        keyring
+    }
+
+    /// Adds a new data key and makes it the active one.
+    fn add_key(&mut self) {
+        let id = Uuid::new_v4().to_string();
+        self.keys
+            .push((id.clone(), XChaCha20Poly1305::generate_key(&mut OsRng)));
+        self.active = id;
+    }
+
+    fn key(&self, id: &str) -> Result<&Key> {
+        self.keys
+            .iter()
+            .find(|(key_id, _)| key_id == id)
+            .map(|(_, key)| key)
+            .with_context(|| format!("Unknown encryption key {}", id))
+    }
+}
+
+enum KeyState {
+    Disabled,
+    Locked,
+    Unlocked(Keyring),
+}
+
+/// Whether the store is encrypted and, if so, its keys once unlocked. Kept
+/// for the whole process rather than in `AppState`, since sessions are read
+/// and written through free functions all over `storage`.
+static KEYS: Mutex<KeyState> = Mutex::new(KeyState::Disabled);
+
+fn set_state(state: KeyState) {
+    *KEYS.lock().unwrap() = state;
+}
+
+/// Reads whether the store in `data_dir` is encrypted; it starts out locked.
+pub fn init(data_dir: &Path) {
+    let state = match EncryptionConfig::load(data_dir) {
+        Ok(Some(_)) => KeyState::Locked,
+        Ok(None) => KeyState::Disabled,
+        Err(err) => {
+            // Better to refuse saving than to save in plain text
+            log::error!(
+                "Failed to load encryption config, keeping the store locked: {:?}",
+                err
+            );
+            KeyState::Locked
+        }
+    };
+    set_state(state);
+}
+
+/// Whether the store is encrypted and still waiting for the passphrase.
+pub fn is_locked() -> bool {
+    matches!(*KEYS.lock().unwrap(), KeyState::Locked)
+}
+
+/// Whether the store is encrypted, locked or not.
+pub fn is_enabled() -> bool {
+    !matches!(*KEYS.lock().unwrap(), KeyState::Disabled)
+}
+
+/// Authenticated data of a session encrypted with `key_id`, so its ciphertext
+/// can't be passed off as another session's.
+fn session_aad(key_id: &str, session_id: &str) -> Vec<u8> {
+    format!("{}/{}", key_id, session_id).into_bytes()
+}
+
+/// Encrypts a session document if encryption is enabled.
+pub fn seal(document: Value) -> Result<Value> {
+    match &*KEYS.lock().unwrap() {
+        KeyState::Disabled => Ok(document),
+        KeyState::Locked => bail!("The session store is locked"),
+        KeyState::Unlocked(keyring) => keyring.seal(document),
+    }
+}
+
+/// Decrypts a stored session document. Unencrypted documents are refused
+/// while encryption is enabled, so none can be slipped into the store.
+pub fn open(document: Value) -> Result<Value> {
+    open_document(document, false)
+}
+
+/// Like `open`, but also takes unencrypted documents, for sessions stored
+/// before encryption was enabled: in backups, or in a store that was
+/// interrupted while switching.
+pub fn open_any(document: Value) -> Result<Value> {
+    open_document(document, true)
+}
+
+fn open_document(document: Value, allow_plaintext: bool) -> Result<Value> {
+    let Some(envelope) = document.get(ENVELOPE_FIELD) else {
+        if !allow_plaintext && is_enabled() {
+            bail!("Unencrypted session in an encrypted session store");
+        }
+        return Ok(document);
+    };
+
+    let keys = KEYS.lock().unwrap();
+    let KeyState::Unlocked(keyring) = &*keys else {
+        bail!("The session store is locked");
+    };
+    keyring.open(envelope)
+}
+
+impl Keyring {
+    /// Encrypts a session document with the active key.
+    fn seal(&self, document: Value) -> Result<Value> {
+        let session_id = document
+            .get("id")
+            .and_then(Value::as_str)
+            .context("Session has no ID")?;
+
+        let cipher = XChaCha20Poly1305::new(self.key(&self.active)?);
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(&document)?;
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: &session_aad(&self.active, session_id),
+                },
+            )
+            .map_err(|_| anyhow!("Failed to encrypt session"))?;
+
+        Ok(serde_json::json!({
+            ENVELOPE_FIELD: {
+                "key_id": self.active,
+                "session_id": session_id,
+                "nonce": STANDARD.encode(nonce),
+                "ciphertext": STANDARD.encode(ciphertext),
+            }
+        }))
+    }
+
+    /// Decrypts the `envelope` of an encrypted session document.
+    fn open(&self, envelope: &Value) -> Result<Value> {
+        let field = |name: &str| {
+            envelope
+                .get(name)
+                .and_then(Value::as_str)
+                .with_context(|| format!("Encrypted session has no {}", name))
+        };
+        let key_id = field("key_id")?;
+        let session_id = field("session_id")?;
+        let nonce = decode_nonce(field("nonce")?)?;
+        let ciphertext = STANDARD
+            .decode(field("ciphertext")?)
+            .context("Invalid ciphertext")?;
+
+        let cipher = XChaCha20Poly1305::new(self.key(key_id)?);
+        let plaintext = cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: &ciphertext,
+                    aad: &session_aad(key_id, session_id),
+                },
+            )
+            .map_err(|_| anyhow!("Session failed to decrypt; it was altered or is corrupt"))?;
+
+        let document: Value =
+            serde_json::from_slice(&plaintext).context("Failed to parse decrypted session")?;
+        if document.get("id").and_then(Value::as_str) != Some(session_id) {
+            bail!("Encrypted session {} holds a different session", session_id);
+        }
+        Ok(document)
+    }
+}
+
+/// Session files of every project, including archived and trashed ones.
+fn session_files(data_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for dir in storage::session_dirs(data_dir)? {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if storage::is_session_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Switches the store to `next`, rewriting every session under it. `config`
+/// is saved before and removed after, if `None`, so a store interrupted half
+/// way can still be read once unlocked, and switching again picks up the rest.
+fn switch_to(data_dir: &Path, next: KeyState, config: Option<EncryptionConfig>) -> Result<usize> {
+    // Nothing is written unless every session can be read with the current keys
+    let mut sessions = Vec::new();
+    for path in session_files(data_dir)? {
+        let session = storage::read_any_session(&path)?;
+        sessions.push((path, session));
+    }
+
+    if let Some(config) = &config {
+        config.save(data_dir)?;
+    }
+
+    let previous = std::mem::replace(&mut *KEYS.lock().unwrap(), next);
+
+    for (path, session) in &sessions {
+        if let Err(err) = storage::rewrite_session_file(path, session) {
+            if config.is_none() {
+                // Still encrypted in part; keep the keys to read those
+                set_state(previous);
+            }
+            return Err(err);
+        }
+    }
+
+    if config.is_none() {
+        let path = EncryptionConfig::path(data_dir);
+        fs::remove_file(&path).with_context(|| format!("Failed to delete {:?}", path))?;
+        remove_backup(&path)?;
+    }
+
+    Ok(sessions.len())
+}
+
+fn data_dir(app_handle: &AppHandle) -> PathBuf {
+    let state = app_handle.state::<crate::AppState>();
+    let storage = state.storage.lock().unwrap();
+    storage.data_dir().to_path_buf()
+}
+
+/// Shows the window so the passphrase can be entered, if the store is locked.
+pub fn request_unlock(app_handle: &AppHandle) {
+    if !is_locked() {
+        return;
+    }
+
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+
+    app_handle
+        .emit("storage-locked", ())
+        .unwrap_or_else(|e| log::error!("Failed to emit storage-locked event: {:?}", e));
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn get_encryption_status() -> Result<EncryptionStatus, String> {
+    let keys = KEYS.lock().map_err(|e| e.to_string())?;
+    Ok(match &*keys {
+        KeyState::Disabled => EncryptionStatus {
+            enabled: false,
+            unlocked: true,
+        },
+        KeyState::Locked => EncryptionStatus {
+            enabled: true,
+            unlocked: false,
+        },
+        KeyState::Unlocked(_) => EncryptionStatus {
+            enabled: true,
+            unlocked: true,
+        },
+    })
+}
+
+#[tauri::command]
+pub async fn unlock_storage(passphrase: String, handle: tauri::AppHandle) -> Result<(), String> {
+    let config = EncryptionConfig::load(&data_dir(&handle))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "The session store is not encrypted".to_string())?;
+
+    let keyring = config.unlock(&passphrase).map_err(|e| e.to_string())?;
+    set_state(KeyState::Unlocked(keyring));
+
+    handle
+        .emit("storage-unlocked", ())
+        .unwrap_or_else(|e| log::error!("Failed to emit storage-unlocked event: {:?}", e));
+    Ok(())
+}
+
+/// Encrypts every stored session with a new key under `passphrase`. Returns
+/// how many sessions were encrypted.
+#[tauri::command]
+pub async fn enable_encryption(
+    passphrase: String,
+    handle: tauri::AppHandle,
+) -> Result<usize, String> {
+    if passphrase.is_empty() {
+        return Err("The passphrase can't be empty".to_string());
+    }
+    let sync_enabled = {
+        let state = handle.state::<crate::AppState>();
+        let settings = state.settings.lock().map_err(|e| e.to_string())?;
+        settings.sync.enabled
+    };
+    if sync_enabled {
+        return Err("Turn off sync first; it shares sessions unencrypted".to_string());
+    }
+    let data_dir = data_dir(&handle);
+    if EncryptionConfig::load(&data_dir)
+        .map_err(|e| e.to_string())?
+        .is_some()
+    {
+        return Err("The session store is already encrypted".to_string());
+    }
+
+    let keyring = Keyring::generate();
+    let config = EncryptionConfig::wrap(&keyring, &passphrase).map_err(|e| e.to_string())?;
+    switch_to(&data_dir, KeyState::Unlocked(keyring), Some(config)).map_err(|e| e.to_string())
+}
+
+/// Decrypts every stored session and turns encryption off.
+#[tauri::command]
+pub async fn disable_encryption(
+    passphrase: String,
+    handle: tauri::AppHandle,
+) -> Result<usize, String> {
+    let data_dir = data_dir(&handle);
+    let config = EncryptionConfig::load(&data_dir)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "The session store is not encrypted".to_string())?;
+
+    // Check the passphrase even if already unlocked
+    let keyring = config.unlock(&passphrase).map_err(|e| e.to_string())?;
+    set_state(KeyState::Unlocked(keyring));
+
+    switch_to(&data_dir, KeyState::Disabled, None).map_err(|e| e.to_string())
+}
+
+/// Changes the passphrase. With `rotate_key`, also encrypts every session
+/// with a new data key, for when the old one may have leaked.
+#[tauri::command]
+pub async fn change_passphrase(
+    current: String,
+    new: String,
+    rotate_key: bool,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
+    if new.is_empty() {
+        return Err("The passphrase can't be empty".to_string());
+    }
+    let data_dir = data_dir(&handle);
+    let config = EncryptionConfig::load(&data_dir)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "The session store is not encrypted".to_string())?;
+
+    let mut keyring = config.unlock(&current).map_err(|e| e.to_string())?;
+    set_state(KeyState::Unlocked(keyring.clone()));
+
+    if rotate_key {
+        keyring.add_key();
+    }
+    let config = EncryptionConfig::wrap(&keyring, &new).map_err(|e| e.to_string())?;
+
+    if rotate_key {
+        switch_to(&data_dir, KeyState::Unlocked(keyring), Some(config))
+            .map_err(|e| e.to_string())?;
+    } else {
+        config.save(&data_dir).map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // These go through `Keyring` and `EncryptionConfig` directly rather than
+    // `seal` and `open`, so they don't change the store's state for other tests.
+
+    fn session(id: &str) -> Value {
+        json!({ "id": id, "name": "Episode 12", "markers": [{ "label": "Intro" }] })
+    }
+
+    fn envelope(sealed: &Value) -> &Value {
+        &sealed[ENVELOPE_FIELD]
+    }
+
+    #[test]
+    fn sealed_session_opens_again() {
+        let keyring = Keyring::generate();
+        let document = session("a");
+
+        let sealed = keyring.seal(document.clone()).unwrap();
+        assert!(!sealed.to_string().contains("Episode 12"));
+        assert_eq!(envelope(&sealed)["key_id"], keyring.active);
+
+        assert_eq!(keyring.open(envelope(&sealed)).unwrap(), document);
+    }
+
+    #[test]
+    fn tampered_session_is_refused() {
+        let keyring = Keyring::generate();
+        let sealed = keyring.seal(session("a")).unwrap();
+
+        let mut ciphertext = STANDARD
+            .decode(envelope(&sealed)["ciphertext"].as_str().unwrap())
+            .unwrap();
+        ciphertext[0] ^= 1;
+        let mut tampered = envelope(&sealed).clone();
+        tampered["ciphertext"] = STANDARD.encode(ciphertext).into();
+        assert!(keyring.open(&tampered).is_err());
+
+        // Passed off as another session
+        let mut renamed = envelope(&sealed).clone();
+        renamed["session_id"] = "b".into();
+        assert!(keyring.open(&renamed).is_err());
+
+        // Sealed for another session, but holding this one
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(&session("a")).unwrap();
+        let ciphertext = XChaCha20Poly1305::new(keyring.key(&keyring.active).unwrap())
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: &session_aad(&keyring.active, "b"),
+                },
+            )
+            .unwrap();
+        let swapped = json!({
+            "key_id": keyring.active,
+            "session_id": "b",
+            "nonce": STANDARD.encode(nonce),
+            "ciphertext": STANDARD.encode(ciphertext),
+        });
+        assert!(keyring.open(&swapped).is_err());
+    }
+
+    #[test]
+    fn wrong_passphrase_is_refused() {
+        let keyring = Keyring::generate();
+        let config = EncryptionConfig::wrap(&keyring, "correct horse").unwrap();
+
+        assert!(config.unlock("battery staple").is_err());
+
+        let unlocked = config.unlock("correct horse").unwrap();
+        let sealed = keyring.seal(session("a")).unwrap();
+        assert_eq!(unlocked.open(envelope(&sealed)).unwrap(), session("a"));
+    }
+
+    #[test]
+    fn changed_passphrase_replaces_the_old_one() {
+        let data_dir =
+            std::env::temp_dir().join(format!("markermoment-encryption-{}", Uuid::new_v4()));
+        fs::create_dir_all(&data_dir).unwrap();
+
+        let keyring = Keyring::generate();
+        EncryptionConfig::wrap(&keyring, "old").unwrap().save(&data_dir).unwrap();
+        EncryptionConfig::wrap(&keyring, "new").unwrap().save(&data_dir).unwrap();
+
+        // No backup is left that the old passphrase still unlocks
+        let path = EncryptionConfig::path(&data_dir);
+        assert!(!storage::backup_path(&path).exists());
+
+        let config = EncryptionConfig::load(&data_dir).unwrap().unwrap();
+        assert!(config.unlock("old").is_err());
+        let unlocked = config.unlock("new").unwrap();
+        assert_eq!(unlocked.active, keyring.active);
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn rotated_key_reencrypts_sessions() {
+        let keyring = Keyring::generate();
+        let sealed = keyring.seal(session("a")).unwrap();
+
+        let mut rotated = keyring.clone();
+        rotated.add_key();
+        let resealed = rotated
+            .seal(rotated.open(envelope(&sealed)).unwrap())
+            .unwrap();
+
+        assert_ne!(rotated.active, keyring.active);
+        assert_eq!(envelope(&resealed)["key_id"], rotated.active);
+        assert!(keyring.open(envelope(&resealed)).is_err());
+
+        // The old key is kept for sessions in older backups
+        let unlocked = EncryptionConfig::wrap(&rotated, "new")
+            .unwrap()
+            .unlock("new")
+            .unwrap();
+        assert_eq!(unlocked.active, rotated.active);
+        assert_eq!(unlocked.open(envelope(&resealed)).unwrap(), session("a"));
+        assert_eq!(unlocked.open(envelope(&sealed)).unwrap(), session("a"));
+    }
+}
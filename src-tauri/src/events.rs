@@ -0,0 +1,190 @@
+//! Application events, delivered to the UI, user configured hooks and webhooks.
+
+use crate::{
+    hooks,
+    session::{Marker, Session},
+    storage::ExportFormat,
+    webhooks,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter as _};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventKind {
+    SessionStarted,
+    SessionStopped,
+    MarkerAdded,
+    ExportFinished,
+}
+
+impl EventKind {
+    /// The name the event is emitted under, e.g. `session-started`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::SessionStarted => "session-started",
+            EventKind::SessionStopped => "session-stopped",
+            EventKind::MarkerAdded => "marker-added",
+            EventKind::ExportFinished => "export-finished",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum AppEvent {
+    SessionStarted {
+        session_id: String,
+        start_time: DateTime<Utc>,
+    },
+    SessionStopped {
+        session: Session,
+    },
+    MarkerAdded {
+        session_id: String,
+        marker: Marker,
+    },
+    ExportFinished {
+        session_id: String,
+        format: ExportFormat,
+        path: PathBuf,
+    },
+}
+
+impl AppEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            AppEvent::SessionStarted { .. } => EventKind::SessionStarted,
+            AppEvent::SessionStopped { .. } => EventKind::SessionStopped,
+            AppEvent::MarkerAdded { .. } => EventKind::MarkerAdded,
+            AppEvent::ExportFinished { .. } => EventKind::ExportFinished,
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        match self {
+            AppEvent::SessionStarted { session_id, .. }
+            | AppEvent::MarkerAdded { session_id, .. }
+            | AppEvent::ExportFinished { session_id, .. } => session_id,
+            AppEvent::SessionStopped { session } => &session.id,
+        }
+    }
+
+    /// Environment variables describing the event, passed to hook commands.
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("MARKERMOMENT_EVENT", self.kind().name().to_string()),
+            ("MARKERMOMENT_SESSION_ID", self.session_id().to_string()),
+        ];
+
+        match self {
+            AppEvent::SessionStarted { start_time, .. } => {
+                vars.push(("MARKERMOMENT_START_TIME", start_time.to_rfc3339()));
+            }
+            AppEvent::SessionStopped { session } => {
+                vars.push(("MARKERMOMENT_START_TIME", session.start_time.to_rfc3339()));
+                vars.push(("MARKERMOMENT_END_TIME", session.end_time.to_rfc3339()));
+                vars.push(("MARKERMOMENT_MARKER_COUNT", session.markers.len().to_string()));
+            }
+            AppEvent::MarkerAdded { marker, .. } => {
+                vars.push(("MARKERMOMENT_MARKER_ID", marker.id.clone()));
+                vars.push(("MARKERMOMENT_MARKER_LABEL", marker.label.clone()));
+                vars.push(("MARKERMOMENT_MARKER_TIMESTAMP", marker.timestamp.to_rfc3339()));
+            }
+            AppEvent::ExportFinished { format, path, .. } => {
+                vars.push(("MARKERMOMENT_EXPORT_FORMAT", format!("{:?}", format)));
+                vars.push(("MARKERMOMENT_EXPORT_PATH", path.to_string_lossy().into_owned()));
+            }
+        }
+
+        vars
+    }
+}
+
+/// Emits `event` to the UI, runs any hooks configured for it and queues webhooks.
+pub fn dispatch(app_handle: &AppHandle, event: AppEvent) {
+    let name = event.kind().name();
+
+    // The UI payloads predate hooks, so keep them as they were
+    let result = match &event {
+        AppEvent::SessionStarted { session_id, .. } => app_handle.emit(name, session_id),
+        AppEvent::SessionStopped { session } => app_handle.emit(name, session),
+        AppEvent::MarkerAdded { marker, .. } => app_handle.emit(name, marker),
+        AppEvent::ExportFinished { .. } => app_handle.emit(name, &event),
+    };
+
+    result.unwrap_or_else(|e| log::error!("Failed to emit {} event: {:?}", name, e));
+
+    hooks::run_hooks(app_handle, &event);
+    webhooks::enqueue(app_handle, &event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn marker_added() -> AppEvent {
+        AppEvent::MarkerAdded {
+            session_id: "session".to_string(),
+            marker: Marker {
+                id: "marker".to_string(),
+                timestamp: "2026-01-01T10:00:00Z".parse().unwrap(),
+                label: "Guest joins".to_string(),
+                notes: None,
+                category: None,
+                recording_offset_ms: Some(1_500),
+                elapsed_ms: Some(1_500),
+                duration_ms: None,
+            },
+        }
+    }
+
+    #[test]
+    fn payload_is_tagged_with_the_event_name() {
+        let event = marker_added();
+        let payload = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(event.kind().name(), "marker-added");
+        assert_eq!(payload["event"], json!(event.kind()));
+        assert_eq!(payload["event"], "marker-added");
+        assert_eq!(payload["session_id"], "session");
+        assert_eq!(payload["marker"]["label"], "Guest joins");
+    }
+
+    #[test]
+    fn env_vars_describe_the_event() {
+        let vars = marker_added().env_vars();
+        let var = |name: &str| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        assert_eq!(var("MARKERMOMENT_EVENT"), Some("marker-added"));
+        assert_eq!(var("MARKERMOMENT_SESSION_ID"), Some("session"));
+        assert_eq!(var("MARKERMOMENT_MARKER_ID"), Some("marker"));
+        assert_eq!(var("MARKERMOMENT_MARKER_LABEL"), Some("Guest joins"));
+        assert_eq!(
+            var("MARKERMOMENT_MARKER_TIMESTAMP"),
+            Some("2026-01-01T10:00:00+00:00")
+        );
+    }
+
+    #[test]
+    fn export_event_names_its_file() {
+        let event = AppEvent::ExportFinished {
+            session_id: "session".to_string(),
+            format: ExportFormat::CSV,
+            path: PathBuf::from("/exports/session.csv"),
+        };
+
+        assert_eq!(event.session_id(), "session");
+        assert!(event
+            .env_vars()
+            .contains(&("MARKERMOMENT_EXPORT_PATH", "/exports/session.csv".to_string())));
+        assert_eq!(serde_json::to_value(&event).unwrap()["format"], "CSV");
+    }
+}
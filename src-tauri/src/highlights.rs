@@ -0,0 +1,468 @@
+//! Suggests highlights from clusters of markers.
+//!
+//! Markers are counted in windows sliding over the session in steps of a
+//! quarter window. Windows with at least `min_markers` markers are joined
+//! where they overlap. Each group becomes a highlight that runs from its first
+//! marker to its last, plus some padding. Highlights can be added to the
+//! session as range markers or exported as a CSV cut list.
+
+use crate::{
+    cut_list::{self, CutList, CutListFormat, CutListOptions},
+    session::{self, Marker, Session},
+    undo::{self, Change},
+};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Label and category of markers made from highlights.
+const HIGHLIGHT: &str = "Highlight";
+
+/// Shorter windows would mostly split a single burst of markers apart.
+const MIN_WINDOW_MS: u64 = 1_000;
+
+/// A day covers any real session whole; capping windows and padding there
+/// keeps the arithmetic on times far from overflowing.
+const MAX_SPAN_MS: u64 = 24 * 60 * 60 * 1_000;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct HighlightOptions {
+    /// Length of the sliding window.
+    pub window_ms: u64,
+    /// Markers a window needs to count as part of a highlight.
+    pub min_markers: usize,
+    /// Added before the first and after the last marker of a highlight.
+    pub padding_ms: u64,
+}
+
+impl Default for HighlightOptions {
+    fn default() -> Self {
+        Self {
+            window_ms: 60_000,
+            min_markers: 3,
+            padding_ms: 5_000,
+        }
+    }
+}
+
+/// Markers in the window starting at `start_ms`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DensityWindow {
+    pub start_ms: i64,
+    pub markers: usize,
+}
+
+/// A suggested highlight. Times are since the session started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Highlight {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    /// Markers inside the highlight.
+    pub marker_ids: Vec<String>,
+    /// Most markers in any one window of the highlight.
+    pub peak_markers: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HighlightAnalysis {
+    pub window_ms: u64,
+    pub step_ms: u64,
+    /// Marker count of every window, for plotting.
+    pub density: Vec<DensityWindow>,
+    /// Densest highlight first.
+    pub highlights: Vec<Highlight>,
+}
+
+/// Point markers of `session` with their times since it started, in order.
+/// Range markers are left out so earlier highlights don't count twice.
+fn marker_times(session: &Session) -> Vec<(i64, &Marker)> {
+    let mut times: Vec<(i64, &Marker)> = session
+        .markers
+        .iter()
+        .filter(|marker| marker.duration_ms.is_none())
+        .map(|marker| {
+            (
+                session.session_time(marker).num_milliseconds().max(0),
+                marker,
+            )
+        })
+        .collect();
+    times.sort_by_key(|(time, _)| *time);
+    times
+}
+
+/// How long `session` runs, at least up to just after its last marker.
+fn length_ms(session: &Session, times: &[(i64, &Marker)]) -> i64 {
+    times
+        .last()
+        .map_or(0, |(time, _)| *time + 1)
+        .max(session.duration().num_milliseconds())
+}
+
+pub fn analyze(session: &Session, options: HighlightOptions) -> Result<HighlightAnalysis, String> {
+    if options.window_ms < MIN_WINDOW_MS {
+        return Err(format!(
+            "The window must be at least {} ms long",
+            MIN_WINDOW_MS
+        ));
+    }
+    if options.window_ms > MAX_SPAN_MS {
+        return Err(format!(
+            "The window can be at most {} ms long",
+            MAX_SPAN_MS
+        ));
+    }
+    if options.padding_ms > MAX_SPAN_MS {
+        return Err(format!("The padding can be at most {} ms", MAX_SPAN_MS));
+    }
+    if options.min_markers == 0 {
+        return Err("A highlight needs at least one marker".to_string());
+    }
+
+    let times = marker_times(session);
+    let window = options.window_ms as i64;
+    let step = window / 4;
+    let end = length_ms(session, &times);
+
+    // Count markers with two cursors, as both window edges only move forward
+    let mut density = Vec::new();
+    let (mut first, mut last) = (0, 0);
+    let mut start = 0;
+    while start < end {
+        while first < times.len() && times[first].0 < start {
+            first += 1;
+        }
+        while last < times.len() && times[last].0 < start + window {
+            last += 1;
+        }
+        density.push(DensityWindow {
+            start_ms: start,
+            markers: last - first,
+        });
+        start += step;
+    }
+
+    // Overlapping dense windows as (start, end, peak)
+    let mut groups: Vec<(i64, i64, usize)> = Vec::new();
+    for window_stats in density
+        .iter()
+        .filter(|window_stats| window_stats.markers >= options.min_markers)
+    {
+        let window_end = window_stats.start_ms + window;
+        match groups.last_mut() {
+            Some(group) if window_stats.start_ms < group.1 => {
+                group.1 = window_end;
+                group.2 = group.2.max(window_stats.markers);
+            }
+            _ => groups.push((window_stats.start_ms, window_end, window_stats.markers)),
+        }
+    }
+
+    let padding = options.padding_ms as i64;
+    let mut highlights: Vec<Highlight> = Vec::new();
+    for (group_start, group_end, peak) in groups {
+        let inside: Vec<&(i64, &Marker)> = times
+            .iter()
+            .filter(|(time, _)| *time >= group_start && *time < group_end)
+            .collect();
+        let (Some(first), Some(last)) = (inside.first(), inside.last()) else {
+            continue;
+        };
+
+        let highlight = Highlight {
+            start_ms: (first.0 - padding).max(0),
+            end_ms: (last.0 + padding).min(end),
+            marker_ids: inside.iter().map(|(_, marker)| marker.id.clone()).collect(),
+            peak_markers: peak,
+        };
+
+        // Padding can make neighbouring highlights overlap
+        match highlights.last_mut() {
+            Some(previous) if highlight.start_ms <= previous.end_ms => {
+                previous.end_ms = highlight.end_ms;
+                previous.marker_ids.extend(highlight.marker_ids);
+                previous.peak_markers = previous.peak_markers.max(highlight.peak_markers);
+            }
+            _ => highlights.push(highlight),
+        }
+    }
+
+    highlights.sort_by(|a, b| {
+        b.peak_markers
+            .cmp(&a.peak_markers)
+            .then(b.marker_ids.len().cmp(&a.marker_ids.len()))
+            .then(a.start_ms.cmp(&b.start_ms))
+    });
+
+    Ok(HighlightAnalysis {
+        window_ms: options.window_ms,
+        step_ms: step as u64,
+        density,
+        highlights,
+    })
+}
+
+/// Checks that `highlights` sent back by the UI lie within `session`, as
+/// those from `analyze` do.
+fn validate(session: &Session, highlights: &[Highlight]) -> Result<(), String> {
+    let length = length_ms(session, &marker_times(session));
+
+    for highlight in highlights {
+        if highlight.start_ms < 0
+            || highlight.end_ms < highlight.start_ms
+            || highlight.end_ms > length
+        {
+            return Err(format!(
+                "The highlight from {} ms to {} ms is outside the session",
+                highlight.start_ms, highlight.end_ms
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// A range marker covering `highlight`.
+pub fn to_marker(session: &Session, highlight: &Highlight) -> Marker {
+    let start = Duration::milliseconds(highlight.start_ms);
+
+    // Place it in the OBS recording relative to a marker it contains
+    let recording_offset_ms = session
+        .markers
+        .iter()
+        .filter(|marker| highlight.marker_ids.contains(&marker.id))
+        .find_map(|marker| {
+            let offset_ms = marker.recording_offset_ms?;
+            Some(offset_ms - (session.session_time(marker) - start).num_milliseconds())
+        });
+
+    Marker {
+        id: Uuid::new_v4().to_string(),
+        timestamp: session.start_time + start,
+        label: HIGHLIGHT.to_string(),
+        notes: Some(format!("{} markers", highlight.marker_ids.len())),
+        category: Some(HIGHLIGHT.to_string()),
+        recording_offset_ms,
+        elapsed_ms: Some(highlight.start_ms as u64),
+        duration_ms: Some((highlight.end_ms - highlight.start_ms).max(0) as u64),
+    }
+}
+
+/// A cut list with a cut for each of `highlights`, and no pre- or post-roll.
+fn to_cut_list(session: &Session, highlights: &[Highlight]) -> Result<CutList, String> {
+    let markers: Vec<Marker> = highlights
+        .iter()
+        .map(|highlight| to_marker(session, highlight))
+        .collect();
+    let options = CutListOptions {
+        pre_roll_ms: 0,
+        post_roll_ms: 0,
+        category: Some(HIGHLIGHT.to_string()),
+        marker_ids: Some(markers.iter().map(|marker| marker.id.clone()).collect()),
+    };
+
+    let mut session = session.clone();
+    session.markers.extend(markers);
+    cut_list::build(&session, &options)
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn analyze_highlights(
+    session_id: String,
+    options: Option<HighlightOptions>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<HighlightAnalysis, String> {
+    let session = session::find_session(&app_state, &session_id)?;
+    analyze(&session, options.unwrap_or_default())
+}
+
+/// Adds range markers for `highlights` to the session, as one undo step.
+#[tauri::command]
+pub async fn add_highlight_markers(
+    session_id: String,
+    highlights: Vec<Highlight>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<Marker>, String> {
+    if highlights.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let session = session::find_session(&app_state, &session_id)?;
+    validate(&session, &highlights)?;
+    let markers: Vec<Marker> = highlights
+        .iter()
+        .map(|highlight| to_marker(&session, highlight))
+        .collect();
+
+    session::update_markers(&app_state, &session_id, |existing| {
+        for marker in &markers {
+            session::insert_marker(existing, marker.clone());
+        }
+        Ok(())
+    })?;
+
+    undo::record(
+        &app_state,
+        Change::AddMarkers {
+            session_id,
+            markers: markers.clone(),
+        },
+    );
+
+    Ok(markers)
+}
+
+/// Exports `highlights` as a CSV cut list to a file picked by the user.
+#[tauri::command]
+pub async fn export_highlights(
+    session_id: String,
+    highlights: Vec<Highlight>,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<String, String> {
+    let default_rate = app_state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .default_frame_rate;
+    let session = session::find_session(&app_state, &session_id)?;
+    validate(&session, &highlights)?;
+
+    let clips = to_cut_list(&session, &highlights)?;
+    if clips.cuts.is_empty() {
+        return Err("No highlights to export".to_string());
+    }
+
+    use tauri_plugin_dialog::DialogExt;
+
+    let file_path = handle
+        .dialog()
+        .file()
+        .set_title("Export Clip List")
+        .add_filter("CSV", &["csv"])
+        .blocking_save_file()
+        .ok_or_else(|| "No file selected".to_string())?;
+    let path = file_path
+        .as_path()
+        .ok_or_else(|| "Invalid file path".to_string())?;
+
+    cut_list::export_to(&session, &clips, path, CutListFormat::CSV, default_rate)
+        .map_err(|e| e.to_string())?;
+
+    Ok("Clip list exported successfully".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A marker `seconds` into the session.
+    fn marker(id: &str, seconds: u64) -> Marker {
+        Marker {
+            id: id.to_string(),
+            timestamp: "2026-01-01T10:00:00Z".parse().unwrap(),
+            label: "Marker".to_string(),
+            notes: None,
+            category: None,
+            recording_offset_ms: None,
+            elapsed_ms: Some(seconds * 1_000),
+            duration_ms: None,
+        }
+    }
+
+    /// An hour long session with markers at `seconds`.
+    fn session(seconds: &[u64]) -> Session {
+        let mut session: Session = serde_json::from_value(json!({
+            "id": "session",
+            "start_time": "2026-01-01T10:00:00Z",
+            "end_time": "2026-01-01T11:00:00Z",
+            "markers": [],
+            "name": "Episode 1",
+            "description": null,
+        }))
+        .unwrap();
+        session.markers = seconds
+            .iter()
+            .map(|second| marker(&second.to_string(), *second))
+            .collect();
+        session
+    }
+
+    fn options(window_ms: u64, padding_ms: u64) -> HighlightOptions {
+        HighlightOptions {
+            window_ms,
+            min_markers: 3,
+            padding_ms,
+        }
+    }
+
+    #[test]
+    fn dense_windows_become_one_highlight() {
+        let analysis = analyze(&session(&[10, 20, 30, 1800]), options(60_000, 5_000)).unwrap();
+
+        assert_eq!(analysis.step_ms, 15_000);
+        assert_eq!(analysis.density.len(), 240);
+        assert_eq!(analysis.density[0].markers, 3);
+
+        let [highlight] = analysis.highlights.as_slice() else {
+            panic!("expected one highlight, got {:?}", analysis.highlights);
+        };
+        assert_eq!((highlight.start_ms, highlight.end_ms), (5_000, 35_000));
+        assert_eq!(highlight.marker_ids, ["10", "20", "30"]);
+        assert_eq!(highlight.peak_markers, 3);
+    }
+
+    #[test]
+    fn padding_merges_neighbouring_highlights() {
+        let markers = [100, 101, 102, 170, 171, 172];
+
+        let apart = analyze(&session(&markers), options(10_000, 0)).unwrap();
+        let mut spans: Vec<(i64, i64)> = apart
+            .highlights
+            .iter()
+            .map(|highlight| (highlight.start_ms, highlight.end_ms))
+            .collect();
+        spans.sort();
+        assert_eq!(spans, [(100_000, 102_000), (170_000, 172_000)]);
+
+        let merged = analyze(&session(&markers), options(10_000, 40_000)).unwrap();
+        let [highlight] = merged.highlights.as_slice() else {
+            panic!("expected one highlight, got {:?}", merged.highlights);
+        };
+        assert_eq!((highlight.start_ms, highlight.end_ms), (60_000, 212_000));
+        assert_eq!(highlight.marker_ids.len(), 6);
+        assert_eq!(highlight.peak_markers, 3);
+    }
+
+    #[test]
+    fn padding_stays_within_the_session() {
+        let analysis = analyze(&session(&[1, 2, 3]), options(60_000, 5_000)).unwrap();
+        assert_eq!(analysis.highlights[0].start_ms, 0);
+
+        let analysis = analyze(&session(&[3597, 3598, 3599]), options(60_000, 5_000)).unwrap();
+        assert_eq!(analysis.highlights[0].end_ms, 3_600_000);
+    }
+
+    #[test]
+    fn highlights_outside_the_session_are_refused() {
+        let session = session(&[10, 20, 30]);
+        let highlight = |start_ms, end_ms| Highlight {
+            start_ms,
+            end_ms,
+            marker_ids: Vec::new(),
+            peak_markers: 0,
+        };
+
+        let analysis = analyze(&session, HighlightOptions::default()).unwrap();
+        assert!(validate(&session, &analysis.highlights).is_ok());
+        assert!(validate(&session, &[highlight(0, 3_600_000)]).is_ok());
+
+        assert!(validate(&session, &[highlight(-1, 1_000)]).is_err());
+        assert!(validate(&session, &[highlight(2_000, 1_000)]).is_err());
+        assert!(validate(&session, &[highlight(0, 3_600_001)]).is_err());
+        assert!(validate(&session, &[highlight(i64::MAX, i64::MAX)]).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! User configured shell commands that run when application events fire.
+//!
+//! Each hook receives the event as JSON on stdin and as `MARKERMOMENT_*`
+//! environment variables. Hooks run in the background, are killed after their
+//! timeout and have their output logged.
+
+use crate::{
+    events::{AppEvent, EventKind},
+    AppState,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{process::Stdio, time::Duration};
+use tauri::{AppHandle, Manager};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hook {
+    pub event: EventKind,
+    pub command: String,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Spawns every enabled hook configured for `event`.
+pub fn run_hooks(app_handle: &AppHandle, event: &AppEvent) {
+    let state = app_handle.state::<AppState>();
+    let hooks: Vec<Hook> = match state.settings.lock() {
+        Ok(settings) => settings
+            .hooks
+            .iter()
+            .filter(|hook| hook.enabled && hook.event == event.kind())
+            .cloned()
+            .collect(),
+        Err(err) => {
+            log::error!("Failed to read hook settings: {:?}", err);
+            return;
+        }
+    };
+
+    if hooks.is_empty() {
+        return;
+    }
+
+    let payload = match serde_json::to_string(event) {
+        Ok(payload) => payload,
+        Err(err) => {
+            log::error!("Failed to serialize {} event for hooks: {:?}", event.kind().name(), err);
+            return;
+        }
+    };
+    let env_vars = event.env_vars();
+
+    for hook in hooks {
+        let payload = payload.clone();
+        let env_vars = env_vars.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match run_hook(&hook, &payload, &env_vars).await {
+                Ok(output) => {
+                    log::info!(
+                        "Hook `{}` for {} exited with {}",
+                        hook.command,
+                        hook.event.name(),
+                        output.status
+                    );
+                    log_output(&hook, "stdout", &output.stdout);
+                    log_output(&hook, "stderr", &output.stderr);
+                }
+                Err(err) => log::error!("Hook `{}` failed: {:?}", hook.command, err),
+            }
+        });
+    }
+}
+
+async fn run_hook(
+    hook: &Hook,
+    payload: &str,
+    env_vars: &[(&'static str, String)],
+) -> Result<std::process::Output> {
+    let mut command = shell_command(&hook.command);
+    command
+        .envs(env_vars.iter().map(|(key, value)| (*key, value.as_str())))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to spawn hook `{}`", hook.command))?;
+
+    // Written while the output is read, as a hook may write before it has
+    // read all of its payload
+    let stdin = child.stdin.take();
+    let write_payload = async move {
+        if let Some(mut stdin) = stdin {
+            // Hooks are free to ignore stdin, so a closed pipe is not an error
+            if let Err(err) = stdin.write_all(payload.as_bytes()).await {
+                log::debug!("Hook `{}` did not read its payload: {:?}", hook.command, err);
+            }
+        }
+    };
+    let execution = async {
+        let (_, output) = tokio::join!(write_payload, child.wait_with_output());
+        output
+    };
+
+    // Dropping the child on timeout kills it
+    tokio::time::timeout(Duration::from_secs(hook.timeout_secs), execution)
+        .await
+        .map_err(|_| anyhow!("Hook `{}` timed out after {}s", hook.command, hook.timeout_secs))?
+        .with_context(|| format!("Failed to wait for hook `{}`", hook.command))
+}
+
+#[cfg(target_os = "windows")]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+fn log_output(hook: &Hook, stream: &str, output: &[u8]) {
+    let output = String::from_utf8_lossy(output);
+    let output = output.trim();
+
+    if !output.is_empty() {
+        log::info!("Hook `{}` {}: {}", hook.command, stream, output);
+    }
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+
+    fn hook(command: &str, timeout_secs: u64) -> Hook {
+        Hook {
+            event: EventKind::MarkerAdded,
+            command: command.to_string(),
+            timeout_secs,
+            enabled: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn hook_gets_payload_and_environment() {
+        let env_vars = [("MARKERMOMENT_EVENT", "marker-added".to_string())];
+        let output = run_hook(
+            &hook("cat; printf ' %s' \"$MARKERMOMENT_EVENT\"", 5),
+            r#"{"event":"marker-added"}"#,
+            &env_vars,
+        )
+        .await
+        .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            r#"{"event":"marker-added"} marker-added"#
+        );
+    }
+
+    #[tokio::test]
+    async fn hook_writing_before_reading_does_not_block() {
+        // Both well past what a pipe buffers
+        let payload = "x".repeat(1 << 20);
+        let output = run_hook(
+            &hook("head -c 1048576 /dev/zero; cat >/dev/null", 10),
+            &payload,
+            &[],
+        )
+        .await
+        .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), 1 << 20);
+    }
+
+    #[tokio::test]
+    async fn hook_is_stopped_after_its_timeout() {
+        let started = std::time::Instant::now();
+        let result = run_hook(&hook("sleep 10", 1), "{}", &[]).await;
+
+        assert!(result.unwrap_err().to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn hook_ignoring_its_payload_still_runs() {
+        let payload = "x".repeat(1 << 20);
+        let output = run_hook(&hook("exit 3", 5), &payload, &[]).await.unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+    }
+}
@@ -1,8 +1,12 @@
-use crate::session::ActiveSession;
+use crate::events::{self, AppEvent};
+use crate::AppState;
+use crate::session::{self, ActiveSession, Session};
 use crate::storage::Storage;
+use crate::templates::SessionTemplate;
+use crate::undo::{self, Change};
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Emitter as _};
-use tauri_plugin_global_shortcut::{Code, Modifiers};
+use tauri::{AppHandle, Emitter as _, Manager};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut};
 
 // Shortcut definitions
 pub const DEFAULT_START_STOP_SHORTCUT: (&str, Modifiers, Code) = (
@@ -17,6 +21,12 @@ pub const DEFAULT_MARKER_SHORTCUT: (&str, Modifiers, Code) = (
     Code::Numpad3,
 );
 
+pub const DEFAULT_UNDO_MARKER_SHORTCUT: (&str, Modifiers, Code) = (
+    "undo_marker",
+    Modifiers::ALT,
+    Code::Numpad1,
+);
+
 pub fn handle_start_stop(
     app_handle: &AppHandle,
     active_session: &Arc<Mutex<Option<ActiveSession>>>,
@@ -24,83 +34,119 @@ pub fn handle_start_stop(
 ) {
     let mut active_session_guard = active_session.lock().unwrap();
 
-    match *active_session_guard {
-        Some(ref active) => {
-            // Stop the session
-            let completed_session = active.to_session();
-
-            // Save the session to storage
-            if let Ok(mut storage_guard) = storage.lock() {
-                if let Err(err) = storage_guard.save_session(&completed_session) {
-                    log::error!("Failed to save session: {:?}", err);
-                    // Notify user of error
-                    show_notification(app_handle, "Error", "Failed to save session");
-                } else {
-                    // Notify user of successful stop
-                    show_notification(
-                        app_handle,
-                        "Session Stopped",
-                        &format!(
-                            "Session duration: {}m",
-                            (completed_session.end_time - completed_session.start_time)
-                                .num_minutes()
-                        ),
-                    );
-
-                    // Emit event to UI
-                    app_handle
-                        .emit("session-stopped", completed_session)
-                        .unwrap_or_else(|e| {
-                            log::error!("Failed to emit session-stopped event: {:?}", e)
-                        });
-                }
-            }
+    if active_session_guard.is_some() {
+        stop_session(app_handle, &mut active_session_guard, storage);
+    } else {
+        start_session(app_handle, &mut active_session_guard, ActiveSession::new());
+    }
+}
 
-            // Clear active session
-            *active_session_guard = None;
-        }
-        None => {
-            // Start a new session
-            let new_session = ActiveSession::new();
+/// Makes `new_session` the active session and notifies the UI.
+pub fn start_session(
+    app_handle: &AppHandle,
+    active_session: &mut Option<ActiveSession>,
+    new_session: ActiveSession,
+) {
+    // Notify user
+    show_notification(
+        app_handle,
+        "Session Started",
+        "Use Ctrl+Shift+M to add markers",
+    );
 
-            // Notify user
-            show_notification(
-                app_handle,
-                "Session Started",
-                "Use Ctrl+Shift+M to add markers",
-            );
+    events::dispatch(
+        app_handle,
+        AppEvent::SessionStarted {
+            session_id: new_session.id.clone(),
+            start_time: new_session.start_time,
+        },
+    );
 
-            // Emit event to UI
-            app_handle
-                .emit("session-started", &new_session.id)
-                .unwrap_or_else(|e| log::error!("Failed to emit session-started event: {:?}", e));
+    // Set as active session
+    *active_session = Some(new_session);
+}
 
-            // Set as active session
-            *active_session_guard = Some(new_session);
-        }
+/// Stops and saves the active session, if any, and notifies the UI. If it
+/// can't be saved, e.g. while the store is locked, it stays active.
+pub fn stop_session(
+    app_handle: &AppHandle,
+    active_session: &mut Option<ActiveSession>,
+    storage: &Arc<Mutex<Storage>>,
+) -> Option<Session> {
+    // Clear active session
+    let active = active_session.take()?;
+    let mut completed_session = active.to_session();
+
+    let state = app_handle.state::<AppState>();
+    if let Ok(scripts) = state.scripts.lock() {
+        scripts.process_session_stop(&mut completed_session);
     }
+
+    // Save the session to storage
+    let saved = match storage.lock() {
+        Ok(mut storage_guard) => storage_guard
+            .save_session(&completed_session)
+            .map_err(|e| format!("{:?}", e)),
+        Err(err) => Err(err.to_string()),
+    };
+
+    if let Err(err) = saved {
+        log::error!("Failed to save session: {}", err);
+        // Keep it running rather than lose it
+        *active_session = Some(active);
+        // Notify user of error
+        show_notification(app_handle, "Error", "Failed to save session");
+        crate::encryption::request_unlock(app_handle);
+        return None;
+    }
+
+    // Notify user of successful stop
+    show_notification(
+        app_handle,
+        "Session Stopped",
+        &format!(
+            "Session duration: {}m",
+            completed_session.duration().num_minutes()
+        ),
+    );
+
+    events::dispatch(
+        app_handle,
+        AppEvent::SessionStopped {
+            session: completed_session.clone(),
+        },
+    );
+    session::request_details(app_handle, &completed_session);
+
+    Some(completed_session)
+}
+
+/// Starts a session from `template` unless one is already running.
+pub fn start_template_session(app_handle: &AppHandle, template: &SessionTemplate) {
+    let state = app_handle.state::<AppState>();
+    let mut active_session_guard = state.active_session.lock().unwrap();
+
+    if active_session_guard.is_some() {
+        show_notification(
+            app_handle,
+            "Session In Progress",
+            "Stop the current session before starting another",
+        );
+        return;
+    }
+
+    let new_session = match state.storage.lock() {
+        Ok(storage) => template.new_session(&storage),
+        Err(_) => return,
+    };
+    start_session(app_handle, &mut active_session_guard, new_session);
 }
 
 pub fn handle_marker(app_handle: &AppHandle, active_session: &Arc<Mutex<Option<ActiveSession>>>) {
     let mut active_session_guard = active_session.lock().unwrap();
 
     match *active_session_guard {
-        Some(ref mut active) => {
-            // Add marker to active session
-            active.add_marker("Marker");
-
-            // Notify user
-            show_notification(
-                app_handle,
-                "Marker Added",
-                "Marker added to current session",
-            );
-
-            // Emit event to UI
-            app_handle
-                .emit("marker-added", active.markers.last().unwrap())
-                .unwrap_or_else(|e| log::error!("Failed to emit marker-added event: {:?}", e));
-        }
+        Some(ref mut active) => add_marker(app_handle, active, "Marker", None),
         None => {
             // No active session
             show_notification(
@@ -112,6 +158,176 @@ pub fn handle_marker(app_handle: &AppHandle, active_session: &Arc<Mutex<Option<A
     }
 }
 
+fn add_marker(
+    app_handle: &AppHandle,
+    active: &mut ActiveSession,
+    label: &str,
+    category: Option<String>,
+) {
+    // Let scripts adjust or drop the marker before adding it
+    let mut marker = active.new_marker(label);
+    marker.category = category;
+    let state = app_handle.state::<AppState>();
+    let marker = match state.scripts.lock() {
+        Ok(scripts) => scripts.process_marker(active, marker),
+        Err(_) => Some(marker),
+    };
+
+    let Some(marker) = marker else {
+        show_notification(
+            app_handle,
+            "Marker Dropped",
+            "A script dropped this marker",
+        );
+        return;
+    };
+
+    // Add marker to active session
+    let marker = active.push_marker(marker).clone();
+
+    undo::record(
+        &state,
+        Change::AddMarker {
+            session_id: active.id.clone(),
+            marker: marker.clone(),
+        },
+    );
+
+    // Notify user
+    show_notification(
+        app_handle,
+        "Marker Added",
+        "Marker added to current session",
+    );
+
+    events::dispatch(
+        app_handle,
+        AppEvent::MarkerAdded {
+            session_id: active.id.clone(),
+            marker,
+        },
+    );
+}
+
+/// Removes the marker just added to the active session, e.g. after an accidental press.
+pub fn handle_undo_marker(app_handle: &AppHandle) {
+    let session_id = {
+        let state = app_handle.state::<AppState>();
+        let active_session = state.active_session.lock().unwrap();
+        active_session.as_ref().map(|active| active.id.clone())
+    };
+
+    let Some(session_id) = session_id else {
+        show_notification(app_handle, "No Active Session", "There is no marker to undo");
+        return;
+    };
+
+    match undo::undo_last_marker(app_handle, &session_id) {
+        Ok(true) => show_notification(app_handle, "Marker Removed", "The last marker was undone"),
+        Ok(false) => show_notification(
+            app_handle,
+            "Nothing to Undo",
+            "The last change was not a new marker",
+        ),
+        Err(err) => {
+            log::error!("Failed to undo marker: {}", err);
+            show_notification(app_handle, "Error", "Failed to undo marker");
+        }
+    }
+}
+
+/// Every valid shortcut defined by `templates`, without duplicates.
+fn template_shortcuts(templates: &[SessionTemplate]) -> Vec<Shortcut> {
+    let mut shortcuts = Vec::new();
+
+    for template in templates {
+        let values = template
+            .hotkey
+            .iter()
+            .chain(template.marker_hotkeys.iter().map(|hotkey| &hotkey.shortcut));
+
+        for value in values {
+            match value.parse::<Shortcut>() {
+                Ok(shortcut) if !shortcuts.contains(&shortcut) => shortcuts.push(shortcut),
+                Ok(_) => {}
+                Err(err) => log::warn!(
+                    "Ignoring invalid shortcut {:?} in template {}: {}",
+                    value,
+                    template.name,
+                    err
+                ),
+            }
+        }
+    }
+
+    shortcuts
+}
+
+fn matches_shortcut(value: &str, shortcut: &Shortcut) -> bool {
+    value.parse::<Shortcut>().is_ok_and(|parsed| &parsed == shortcut)
+}
+
+/// Replaces the shortcuts registered for `previous` templates with those of `templates`.
+pub fn register_template_shortcuts(
+    app_handle: &AppHandle,
+    previous: &[SessionTemplate],
+    templates: &[SessionTemplate],
+) {
+    let global_shortcut = app_handle.global_shortcut();
+
+    for shortcut in template_shortcuts(previous) {
+        if let Err(err) = global_shortcut.unregister(shortcut) {
+            log::warn!("Failed to unregister template shortcut: {:?}", err);
+        }
+    }
+
+    for shortcut in template_shortcuts(templates) {
+        if let Err(err) = global_shortcut.register(shortcut) {
+            log::warn!("Failed to register template shortcut: {:?}", err);
+        }
+    }
+}
+
+/// Handles a shortcut defined by a template: a marker hotkey of the running
+/// session's template, or the hotkey that starts a session from a template.
+pub fn handle_template_shortcut(app_handle: &AppHandle, shortcut: &Shortcut) {
+    let state = app_handle.state::<AppState>();
+    let templates = state.settings.lock().unwrap().templates.clone();
+
+    {
+        let mut active_session_guard = state.active_session.lock().unwrap();
+
+        if let Some(active) = active_session_guard.as_mut() {
+            let marker_hotkey = active
+                .template_id
+                .as_deref()
+                .and_then(|id| crate::templates::find(&templates, id))
+                .and_then(|template| {
+                    template
+                        .marker_hotkeys
+                        .iter()
+                        .find(|hotkey| matches_shortcut(&hotkey.shortcut, shortcut))
+                });
+
+            if let Some(hotkey) = marker_hotkey {
+                add_marker(app_handle, active, &hotkey.label, hotkey.category.clone());
+                return;
+            }
+        }
+    }
+
+    let template = templates.iter().find(|template| {
+        template
+            .hotkey
+            .as_deref()
+            .is_some_and(|hotkey| matches_shortcut(hotkey, shortcut))
+    });
+
+    if let Some(template) = template {
+        start_template_session(app_handle, template);
+    }
+}
+
 fn show_notification(app_handle: &AppHandle, title: &str, message: &str) {
     app_handle
         .emit("notification", (title, message))
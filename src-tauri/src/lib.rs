@@ -1,10 +1,40 @@
+mod analytics;
+mod backups;
+mod cut_list;
+mod data_dir;
+mod data_watcher;
+mod encryption;
+mod events;
+mod highlights;
+mod hooks;
 mod hotkey;
+mod media;
+mod migrations;
+mod obs;
+mod projects;
+mod recording_watcher;
+mod scripting;
 mod session;
+mod settings;
+mod split_merge;
 mod storage;
+mod sync;
+mod templates;
+mod timecode;
+mod trash;
 mod tray;
+mod undo;
+mod webhooks;
 
+use obs::ObsClient;
+use projects::ProjectList;
+use recording_watcher::RecordingWatcher;
+use scripting::ScriptEngine;
 use session::ActiveSession;
+use settings::Settings;
 use storage::Storage;
+use undo::UndoStack;
+use webhooks::WebhookQueue;
 use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
 
 use std::sync::{Arc, Mutex};
@@ -16,15 +46,13 @@ use tauri::{
 pub struct AppState {
     pub storage: Arc<Mutex<Storage>>,
     pub active_session: Arc<Mutex<Option<ActiveSession>>>,
-}
-
-impl Default for AppState {
-    fn default() -> Self {
-        Self {
-            storage: Arc::new(Mutex::new(Storage::new())),
-            active_session: Arc::new(Mutex::new(None)),
-        }
-    }
+    pub settings: Arc<Mutex<Settings>>,
+    pub obs: Arc<Mutex<ObsClient>>,
+    pub webhooks: Arc<Mutex<WebhookQueue>>,
+    pub scripts: Arc<Mutex<ScriptEngine>>,
+    pub recording_watcher: Arc<Mutex<RecordingWatcher>>,
+    pub projects: Arc<Mutex<ProjectList>>,
+    pub undo: Arc<Mutex<UndoStack>>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -32,10 +60,43 @@ pub fn run() {
     env_logger::init();
     log::info!("Starting Dibikaandaagozi");
 
+    let data_dir = match data_dir::prepare() {
+        Ok((data_dir, source)) => {
+            log::info!("Using data directory {:?} ({:?})", data_dir, source);
+            data_dir
+        }
+        Err(err) => {
+            data_dir::report_startup_error(err);
+            return;
+        }
+    };
+
     // Initialize application state
+    let mut storage = Storage::new(data_dir);
+    encryption::init(storage.data_dir());
+    let projects = ProjectList::load(storage.data_dir()).unwrap_or_else(|err| {
+        log::error!("Failed to load projects, using the default project: {:?}", err);
+        ProjectList::default()
+    });
+    storage.set_sessions_dir(projects::sessions_dir(storage.data_dir(), &projects.active));
+    let settings = Settings::load(storage.data_dir()).unwrap_or_else(|err| {
+        log::error!("Failed to load settings, using defaults: {:?}", err);
+        Settings::default()
+    });
+    storage.set_sync(sync::target(storage.data_dir(), &settings.sync));
+    let webhooks = WebhookQueue::load(storage.data_dir());
+    let scripts = ScriptEngine::new(storage.data_dir());
+
     let app_state = AppState {
-        storage: Arc::new(Mutex::new(Storage::new())),
+        storage: Arc::new(Mutex::new(storage)),
         active_session: Arc::new(Mutex::new(None)),
+        settings: Arc::new(Mutex::new(settings)),
+        obs: Arc::new(Mutex::new(ObsClient::default())),
+        webhooks: Arc::new(Mutex::new(webhooks)),
+        scripts: Arc::new(Mutex::new(scripts)),
+        recording_watcher: Arc::new(Mutex::new(RecordingWatcher::default())),
+        projects: Arc::new(Mutex::new(projects)),
+        undo: Arc::new(Mutex::new(UndoStack::default())),
     };
 
     tauri::Builder::default()
@@ -57,7 +118,7 @@ pub fn run() {
             // Set up system tray
             let tray_menu = tray::create_tray_menu(&app_handle);
 
-            TrayIconBuilder::new()
+            TrayIconBuilder::with_id(tray::TRAY_ID)
                 .icon(app.default_window_icon().unwrap().clone())
                 .menu(&tray_menu)
                 .on_menu_event(move |app, event| {
@@ -85,6 +146,8 @@ pub fn run() {
 
             let marker_shortcut = Shortcut::new(Some(hotkey::DEFAULT_MARKER_SHORTCUT.1), hotkey::DEFAULT_MARKER_SHORTCUT.2);
 
+            let undo_marker_shortcut = Shortcut::new(Some(hotkey::DEFAULT_UNDO_MARKER_SHORTCUT.1), hotkey::DEFAULT_UNDO_MARKER_SHORTCUT.2);
+
             app.handle().plugin(
                 tauri_plugin_global_shortcut::Builder::new().with_handler(move |app_handle, shortcut, event| {
                     let state = app_handle.state::<AppState>();
@@ -100,6 +163,12 @@ pub fn run() {
                             // println!("Marker Button hit!");
                             hotkey::handle_marker(&app_handle, &move_session);
                         }
+                    } else if shortcut == &undo_marker_shortcut {
+                        if event.state() == ShortcutState::Pressed {
+                            hotkey::handle_undo_marker(&app_handle);
+                        }
+                    } else if event.state() == ShortcutState::Pressed {
+                        hotkey::handle_template_shortcut(&app_handle, shortcut);
                     }
                 })
                 .build(),
@@ -111,6 +180,42 @@ pub fn run() {
             
             app.global_shortcut().register(marker_shortcut)?;
 
+            app.global_shortcut().register(undo_marker_shortcut)?;
+
+            let state = app.state::<AppState>();
+            let settings = state.settings.lock().unwrap().clone();
+
+            // Shortcuts that start template sessions and add template markers
+            hotkey::register_template_shortcuts(app.handle(), &[], &settings.templates);
+
+            // Connect to OBS if enabled
+            state.obs.lock().unwrap().restart(app.handle(), &settings.obs);
+
+            // Watch the recordings folder if enabled
+            state
+                .recording_watcher
+                .lock()
+                .unwrap()
+                .restart(app.handle(), &settings.recording_watcher);
+
+            // Deliver queued webhooks, including any left over from the last run
+            webhooks::spawn_worker(app.handle().clone());
+
+            // Purge sessions that have been in the trash for too long
+            trash::spawn_purger(app.handle().clone());
+
+            // Ask for the passphrase if sessions are encrypted
+            encryption::request_unlock(app.handle());
+
+            // Back up sessions when a scheduled backup is due
+            backups::spawn_scheduler(app.handle().clone());
+
+            // Sync sessions with other devices through the shared folder
+            sync::spawn_syncer(app.handle().clone());
+
+            // Tell the UI about session files changed outside the app
+            data_watcher::spawn_watcher(app.handle().clone());
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -120,7 +225,67 @@ pub fn run() {
             session::get_active_session,
             session::get_sessions,
             session::get_session_by_id,
-            storage::export_session
+            session::get_tags,
+            session::set_active_session_details,
+            session::set_session_details,
+            storage::export_session,
+            storage::check_storage,
+            storage::migrate_sessions,
+            settings::get_settings,
+            settings::update_settings,
+            obs::get_obs_status,
+            webhooks::get_webhook_deliveries,
+            webhooks::get_pending_webhooks,
+            scripting::list_scripts,
+            scripting::reload_scripts,
+            media::attach_media,
+            media::detach_media,
+            media::relocate_media,
+            media::check_media,
+            media::locate_media,
+            media::set_media_offset,
+            timecode::set_session_timecode,
+            projects::list_projects,
+            projects::create_project,
+            projects::rename_project,
+            projects::delete_project,
+            projects::switch_project,
+            projects::get_project_sessions,
+            projects::move_session_to_project,
+            projects::export_project,
+            split_merge::split_session,
+            split_merge::merge_sessions,
+            split_merge::get_archived_sessions,
+            split_merge::restore_archived_session,
+            trash::delete_session,
+            trash::get_trashed_sessions,
+            trash::restore_session,
+            trash::purge_trash,
+            session::edit_marker,
+            session::delete_marker,
+            undo::get_undo_state,
+            undo::undo_change,
+            undo::redo_change,
+            data_dir::get_data_dir,
+            data_dir::set_data_dir,
+            backups::backup_now,
+            backups::list_backups,
+            backups::restore_backup_archive,
+            sync::sync_sessions,
+            sync::get_sync_conflicts,
+            sync::resolve_sync_conflict,
+            encryption::get_encryption_status,
+            encryption::unlock_storage,
+            encryption::enable_encryption,
+            encryption::disable_encryption,
+            encryption::change_passphrase,
+            analytics::get_session_stats,
+            analytics::export_session_stats,
+            highlights::analyze_highlights,
+            highlights::add_highlight_markers,
+            highlights::export_highlights,
+            cut_list::get_cut_list,
+            cut_list::export_cut_list
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,308 @@
+//! References from sessions to the recorded media files they describe.
+
+use crate::session::{MediaOffset, OffsetSource, Session};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+/// How deep `locate_media` searches below the directory it is given.
+const MAX_SEARCH_DEPTH: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub created: Option<DateTime<Utc>>,
+    pub modified: Option<DateTime<Utc>>,
+    /// SHA-256 of the file contents, hex encoded.
+    pub hash: Option<String>,
+}
+
+impl MediaFile {
+    pub fn from_path(path: &Path, compute_hash: bool) -> Result<Self> {
+        let metadata =
+            fs::metadata(path).with_context(|| format!("Failed to read metadata of {:?}", path))?;
+
+        if !metadata.is_file() {
+            bail!("{:?} is not a file", path);
+        }
+
+        let hash = if compute_hash {
+            Some(hash_file(path)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            created: metadata.created().ok().map(DateTime::<Utc>::from),
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            hash,
+        })
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
+    /// Whether `candidate` has the same contents as this file, by size and,
+    /// if known, by hash.
+    pub fn matches(&self, candidate: &Path) -> bool {
+        let size_matches = fs::metadata(candidate)
+            .map(|metadata| metadata.is_file() && metadata.len() == self.size)
+            .unwrap_or(false);
+
+        match (&self.hash, size_matches) {
+            (_, false) => false,
+            (Some(hash), true) => hash_file(candidate).is_ok_and(|other| &other == hash),
+            (None, true) => true,
+        }
+    }
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let file = File::open(path).with_context(|| format!("Failed to open file at {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher).with_context(|| format!("Failed to hash {:?}", path))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Searches `dir` for a moved copy of `media`, preferring files with the same name.
+pub fn find_relocated(media: &MediaFile, dir: &Path) -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    collect_files(dir, MAX_SEARCH_DEPTH, media.size, &mut candidates);
+
+    let file_name = media.path.file_name();
+    candidates.sort_by_key(|candidate| candidate.file_name() != file_name);
+
+    candidates
+        .into_iter()
+        .find(|candidate| media.matches(candidate))
+}
+
+fn collect_files(dir: &Path, depth: usize, size: u64, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.metadata() {
+            Ok(metadata) if metadata.is_file() && metadata.len() == size => files.push(path),
+            Ok(metadata) if metadata.is_dir() && depth > 0 => {
+                collect_files(&path, depth - 1, size, files)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaStatus {
+    pub path: PathBuf,
+    pub exists: bool,
+    /// Whether the file on disk still has the recorded size.
+    pub size_matches: bool,
+}
+
+fn load_session(app_state: &crate::AppState, id: &str) -> Result<Session, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    storage
+        .get_session(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session with ID {} not found", id))
+}
+
+/// Loads, changes and saves a stored session under one storage lock, so
+/// concurrent changes to its media aren't lost.
+fn update_session(
+    app_state: &crate::AppState,
+    id: &str,
+    f: impl FnOnce(&mut Session) -> Result<(), String>,
+) -> Result<Session, String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let mut session = storage
+        .get_session(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session with ID {} not found", id))?;
+
+    f(&mut session)?;
+
+    storage.save_session(&session).map_err(|e| e.to_string())?;
+    Ok(session)
+}
+
+/// Runs file work such as hashing off the async runtime.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+fn attached<'a>(session: &'a mut Session, path: &Path) -> Result<&'a mut MediaFile, String> {
+    session
+        .media
+        .iter_mut()
+        .find(|media| media.path == path)
+        .ok_or_else(|| format!("{:?} is not attached to this session", path))
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn attach_media(
+    session_id: String,
+    path: PathBuf,
+    compute_hash: Option<bool>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    let media = {
+        let path = path.clone();
+        blocking(move || MediaFile::from_path(&path, compute_hash.unwrap_or(false))).await?
+    };
+
+    update_session(&app_state, &session_id, |session| {
+        if session.media.iter().any(|media| media.path == path) {
+            return Err(format!("{:?} is already attached to this session", path));
+        }
+        session.media.push(media);
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub async fn detach_media(
+    session_id: String,
+    path: PathBuf,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    update_session(&app_state, &session_id, |session| {
+        let count = session.media.len();
+        session.media.retain(|media| media.path != path);
+        if session.media.len() == count {
+            return Err(format!("{:?} is not attached to this session", path));
+        }
+        Ok(())
+    })
+}
+
+/// Points an attached file at its new location. Unless `force` is set, the
+/// new file must match the recorded size (and hash, if one was computed).
+#[tauri::command]
+pub async fn relocate_media(
+    session_id: String,
+    old_path: PathBuf,
+    new_path: PathBuf,
+    force: Option<bool>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    let mut session = load_session(&app_state, &session_id)?;
+    let media = attached(&mut session, &old_path)?.clone();
+
+    let relocated = {
+        let new_path = new_path.clone();
+        blocking(move || {
+            if !force.unwrap_or(false) && !media.matches(&new_path) {
+                bail!("{:?} does not match the attached file", new_path);
+            }
+            MediaFile::from_path(&new_path, media.hash.is_some())
+        })
+        .await?
+    };
+
+    update_session(&app_state, &session_id, |session| {
+        *attached(session, &old_path)? = relocated;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub async fn check_media(
+    session_id: String,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<MediaStatus>, String> {
+    let session = load_session(&app_state, &session_id)?;
+
+    Ok(session
+        .media
+        .iter()
+        .map(|media| {
+            let size = fs::metadata(&media.path).ok().map(|metadata| metadata.len());
+            MediaStatus {
+                path: media.path.clone(),
+                exists: size.is_some(),
+                size_matches: size == Some(media.size),
+            }
+        })
+        .collect())
+}
+
+/// Looks for missing attached files below `search_dir` and relinks any it finds.
+#[tauri::command]
+pub async fn locate_media(
+    session_id: String,
+    search_dir: PathBuf,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    let session = load_session(&app_state, &session_id)?;
+    let missing: Vec<MediaFile> = session
+        .media
+        .into_iter()
+        .filter(|media| !media.exists())
+        .collect();
+
+    let found = blocking(move || {
+        Ok(missing
+            .into_iter()
+            .filter_map(|media| {
+                find_relocated(&media, &search_dir).map(|found| (media.path, found))
+            })
+            .collect::<Vec<_>>())
+    })
+    .await?;
+
+    if found.is_empty() {
+        return load_session(&app_state, &session_id);
+    }
+
+    update_session(&app_state, &session_id, |session| {
+        for (path, found) in found {
+            // Skip files detached or relocated while searching
+            if let Ok(media) = attached(session, &path) {
+                log::info!("Relocated {:?} to {:?}", media.path, found);
+                media.path = found;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Sets how far the recording is offset from the session, or clears it if
+/// `source` is `None`. Relative times in exports use the resulting media time.
+#[tauri::command]
+pub async fn set_media_offset(
+    session_id: String,
+    source: Option<OffsetSource>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    update_session(&app_state, &session_id, |session| {
+        session.media_offset = match source {
+            Some(source) => Some(MediaOffset {
+                offset_ms: session.compute_media_offset(&source)?,
+                source,
+            }),
+            None => None,
+        };
+        Ok(())
+    })
+}
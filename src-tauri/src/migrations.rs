@@ -0,0 +1,143 @@
+//! Schema versions of stored sessions and the migrations between them.
+//!
+//! Session files carry a `schema_version`. Files from before versioning have
+//! none and count as version 1. On read, a file is upgraded one version at a
+//! time on its raw JSON, before it is deserialized into a `Session`, so the
+//! structs only ever have to understand the current format.
+//!
+//! To change the format, bump `CURRENT_VERSION`, add a migration from the
+//! previous version to `MIGRATIONS` and add a fixture for the new version.
+
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+
+pub const CURRENT_VERSION: u64 = 2;
+
+const VERSION_FIELD: &str = "schema_version";
+
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// Migrations by the version they upgrade from.
+const MIGRATIONS: &[(u64, Migration)] = &[(1, v1_to_v2)];
+
+/// Version 2 added tags, media links and split/merge history, which version 1
+/// files lack entirely, and stores empty notes as `null`.
+fn v1_to_v2(session: &mut Map<String, Value>) -> Result<()> {
+    for field in ["tags", "media", "derived_from"] {
+        session
+            .entry(field)
+            .or_insert_with(|| Value::Array(Vec::new()));
+    }
+
+    if let Some(Value::Array(markers)) = session.get_mut("markers") {
+        for marker in markers.iter_mut().filter_map(Value::as_object_mut) {
+            if marker.get("notes").and_then(Value::as_str) == Some("") {
+                marker.insert("notes".to_string(), Value::Null);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Upgrades a stored session to the current version in place. Returns whether
+/// anything had to be migrated.
+pub fn migrate_session(session: &mut Value) -> Result<bool> {
+    let Some(object) = session.as_object_mut() else {
+        bail!("Session is not a JSON object");
+    };
+
+    let from = object
+        .get(VERSION_FIELD)
+        .and_then(Value::as_u64)
+        .unwrap_or(1);
+    if from > CURRENT_VERSION {
+        bail!(
+            "Session has schema version {}, but this version of the app only reads up to {}",
+            from,
+            CURRENT_VERSION
+        );
+    }
+
+    for version in from..CURRENT_VERSION {
+        let Some((_, migration)) = MIGRATIONS.iter().find(|(from, _)| *from == version) else {
+            bail!("No migration from schema version {}", version);
+        };
+        migration(object)?;
+        object.insert(VERSION_FIELD.to_string(), Value::from(version + 1));
+    }
+
+    Ok(from < CURRENT_VERSION)
+}
+
+/// `session` as stored, stamped with the current schema version.
+pub fn stamp(mut session: Value) -> Value {
+    if let Some(object) = session.as_object_mut() {
+        object.insert(VERSION_FIELD.to_string(), Value::from(CURRENT_VERSION));
+    }
+    session
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Session;
+
+    const V1: &str = include_str!("../tests/fixtures/sessions/v1.json");
+    const V2: &str = include_str!("../tests/fixtures/sessions/v2.json");
+
+    fn migrated(fixture: &str) -> (Value, bool) {
+        let mut value: Value = serde_json::from_str(fixture).unwrap();
+        let changed = migrate_session(&mut value).unwrap();
+        (value, changed)
+    }
+
+    #[test]
+    fn v1_fixture_migrates_to_current() {
+        let (value, changed) = migrated(V1);
+
+        assert!(changed);
+        assert_eq!(value[VERSION_FIELD], Value::from(CURRENT_VERSION));
+        assert_eq!(value["tags"], Value::Array(Vec::new()));
+        assert_eq!(value["markers"][1]["notes"], Value::Null);
+
+        let session: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(session.markers.len(), 2);
+        assert_eq!(session.markers[0].notes.as_deref(), Some("Intro"));
+    }
+
+    #[test]
+    fn v2_fixture_is_current() {
+        let (value, changed) = migrated(V2);
+
+        assert!(!changed);
+        assert_eq!(value, serde_json::from_str::<Value>(V2).unwrap());
+
+        let session: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(session.tags, vec!["podcast".to_string()]);
+        assert_eq!(session.markers[0].category.as_deref(), Some("Highlight"));
+    }
+
+    #[test]
+    fn every_fixture_round_trips_through_the_current_format() {
+        for fixture in [V1, V2] {
+            let (value, _) = migrated(fixture);
+            let session: Session = serde_json::from_value(value).unwrap();
+
+            let mut stored = stamp(serde_json::to_value(&session).unwrap());
+            assert!(!migrate_session(&mut stored).unwrap());
+
+            let reread: Session = serde_json::from_value(stored).unwrap();
+            assert_eq!(reread.id, session.id);
+            assert_eq!(reread.markers.len(), session.markers.len());
+        }
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut value: Value = serde_json::from_str(V2).unwrap();
+        value[VERSION_FIELD] = Value::from(CURRENT_VERSION + 1);
+
+        assert!(migrate_session(&mut value).is_err());
+    }
+}
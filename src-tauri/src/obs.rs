@@ -0,0 +1,648 @@
+//! Optional OBS WebSocket (protocol v5) client.
+//!
+//! When enabled, the client mirrors OBS recordings: a `RecordStateChanged` start
+//! event starts an `ActiveSession`, the stop event stops and saves it together
+//! with the OBS output file path. While recording, the client polls
+//! `GetRecordStatus` so markers can be stamped with OBS's recording timecode.
+
+use crate::{hotkey, session::ActiveSession, AppState};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tauri::{async_runtime::JoinHandle, AppHandle, Manager};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+const OP_HELLO: u64 = 0;
+const OP_IDENTIFY: u64 = 1;
+const OP_IDENTIFIED: u64 = 2;
+const OP_EVENT: u64 = 5;
+const OP_REQUEST: u64 = 6;
+const OP_REQUEST_RESPONSE: u64 = 7;
+
+const RPC_VERSION: u64 = 1;
+
+/// `EventSubscription::Outputs`, which carries `RecordStateChanged`.
+const EVENT_SUBSCRIPTION_OUTPUTS: u64 = 1 << 6;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+type ObsSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// What a connection does when OBS reports on its recording. The app starts,
+/// re-anchors and stops the active session; tests record the calls instead.
+trait RecordingEvents {
+    /// A recording started at `started_at`, or was found already running.
+    fn started(&self, started_at: DateTime<Utc>, output_path: Option<String>);
+    /// The running recording effectively started at `started_at`, after pauses.
+    fn reanchored(&self, started_at: DateTime<Utc>);
+    fn stopped(&self, output_path: Option<String>);
+}
+
+impl RecordingEvents for AppHandle {
+    fn started(&self, started_at: DateTime<Utc>, output_path: Option<String>) {
+        start_recording_session(self, started_at, output_path);
+    }
+
+    fn reanchored(&self, started_at: DateTime<Utc>) {
+        reanchor_recording_session(self, started_at);
+    }
+
+    fn stopped(&self, output_path: Option<String>) {
+        stop_recording_session(self, output_path);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObsSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub password: Option<String>,
+}
+
+impl Default for ObsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 4455,
+            password: None,
+        }
+    }
+}
+
+impl ObsSettings {
+    pub fn url(&self) -> String {
+        format!("ws://{}:{}", self.host, self.port)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ObsStatus {
+    pub connected: bool,
+    pub recording: bool,
+    pub output_path: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// Owns the background connection task and the status it reports.
+#[derive(Default)]
+pub struct ObsClient {
+    status: Arc<Mutex<ObsStatus>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl ObsClient {
+    /// Drops any existing connection and reconnects with `settings` if enabled.
+    pub fn restart(&mut self, app_handle: &AppHandle, settings: &ObsSettings) {
+        self.stop();
+
+        if settings.enabled {
+            log::info!("Connecting to OBS at {}", settings.url());
+            self.task = Some(spawn(
+                app_handle.clone(),
+                settings.clone(),
+                self.status.clone(),
+            ));
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        if let Ok(mut status) = self.status.lock() {
+            *status = ObsStatus::default();
+        }
+    }
+
+    pub fn status(&self) -> ObsStatus {
+        self.status
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+}
+
+fn spawn(
+    app_handle: AppHandle,
+    settings: ObsSettings,
+    status: Arc<Mutex<ObsStatus>>,
+) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let result = run_connection(&app_handle, &settings, &status).await;
+
+            if let Ok(mut status) = status.lock() {
+                status.connected = false;
+                status.recording = false;
+                if let Err(ref err) = result {
+                    status.last_error = Some(err.to_string());
+                }
+            }
+
+            match result {
+                Ok(()) => log::info!("OBS connection closed"),
+                Err(err) => log::warn!("OBS connection failed: {:?}", err),
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+/// The `authentication` value expected by `Identify`, as described in the
+/// obs-websocket v5 protocol.
+pub fn authentication_string(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = STANDARD.encode(Sha256::digest(format!("{}{}", password, salt)));
+    STANDARD.encode(Sha256::digest(format!("{}{}", secret, challenge)))
+}
+
+/// Formats a recording offset the way OBS reports `outputTimecode` (`HH:MM:SS.mmm`).
+pub fn format_timecode(offset_ms: i64) -> String {
+    let offset_ms = offset_ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        offset_ms / 3_600_000,
+        (offset_ms / 60_000) % 60,
+        (offset_ms / 1000) % 60,
+        offset_ms % 1000
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct ObsMessage {
+    op: u64,
+    #[serde(default)]
+    d: Value,
+}
+
+async fn next_message(socket: &mut ObsSocket) -> Result<Option<ObsMessage>> {
+    while let Some(message) = socket.next().await {
+        match message? {
+            Message::Text(text) => {
+                let message = serde_json::from_str(&text)
+                    .with_context(|| format!("Invalid message from OBS: {}", text))?;
+                return Ok(Some(message));
+            }
+            Message::Close(_) => return Ok(None),
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+async fn expect_message(socket: &mut ObsSocket, op: u64) -> Result<ObsMessage> {
+    match next_message(socket).await? {
+        Some(message) if message.op == op => Ok(message),
+        Some(message) => bail!("Expected OBS op {} but received op {}", op, message.op),
+        None => bail!("OBS closed the connection during the handshake"),
+    }
+}
+
+async fn send_message(socket: &mut ObsSocket, op: u64, d: Value) -> Result<()> {
+    let message = json!({ "op": op, "d": d });
+    socket.send(Message::Text(message.to_string().into())).await?;
+    Ok(())
+}
+
+async fn request_record_status(socket: &mut ObsSocket) -> Result<()> {
+    send_message(
+        socket,
+        OP_REQUEST,
+        json!({
+            "requestType": "GetRecordStatus",
+            "requestId": uuid::Uuid::new_v4().to_string(),
+        }),
+    )
+    .await
+}
+
+async fn run_connection(
+    events: &impl RecordingEvents,
+    settings: &ObsSettings,
+    status: &Arc<Mutex<ObsStatus>>,
+) -> Result<()> {
+    let (mut socket, _) = connect_async(settings.url())
+        .await
+        .with_context(|| format!("Failed to connect to OBS at {}", settings.url()))?;
+
+    let hello = expect_message(&mut socket, OP_HELLO).await?;
+
+    let mut identify = json!({
+        "rpcVersion": RPC_VERSION,
+        "eventSubscriptions": EVENT_SUBSCRIPTION_OUTPUTS,
+    });
+
+    if let Some(auth) = hello.d.get("authentication") {
+        let password = settings
+            .password
+            .as_deref()
+            .ok_or_else(|| anyhow!("OBS requires a password but none is configured"))?;
+        let salt = auth["salt"].as_str().unwrap_or_default();
+        let challenge = auth["challenge"].as_str().unwrap_or_default();
+        identify["authentication"] = json!(authentication_string(password, salt, challenge));
+    }
+
+    send_message(&mut socket, OP_IDENTIFY, identify).await?;
+    expect_message(&mut socket, OP_IDENTIFIED).await?;
+
+    log::info!("Connected to OBS at {}", settings.url());
+    if let Ok(mut status) = status.lock() {
+        status.connected = true;
+        status.last_error = None;
+    }
+
+    // Pick up a recording that was already running before we connected
+    request_record_status(&mut socket).await?;
+
+    let mut poll = tokio::time::interval(STATUS_POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            message = next_message(&mut socket) => {
+                match message? {
+                    Some(message) => handle_message(events, status, message),
+                    None => return Ok(()),
+                }
+            }
+            _ = poll.tick() => {
+                let recording = status.lock().map(|status| status.recording).unwrap_or(false);
+                if recording {
+                    request_record_status(&mut socket).await?;
+                }
+            }
+        }
+    }
+}
+
+fn handle_message(
+    events: &impl RecordingEvents,
+    status: &Arc<Mutex<ObsStatus>>,
+    message: ObsMessage,
+) {
+    match message.op {
+        OP_EVENT if message.d["eventType"] == "RecordStateChanged" => {
+            on_record_state_changed(events, status, &message.d["eventData"]);
+        }
+        OP_REQUEST_RESPONSE if message.d["requestType"] == "GetRecordStatus" => {
+            on_record_status(events, status, &message.d["responseData"]);
+        }
+        _ => {}
+    }
+}
+
+fn on_record_state_changed(
+    events: &impl RecordingEvents,
+    status: &Arc<Mutex<ObsStatus>>,
+    data: &Value,
+) {
+    let output_path = data["outputPath"].as_str().map(str::to_string);
+
+    match data["outputState"].as_str() {
+        Some("OBS_WEBSOCKET_OUTPUT_STARTED") => {
+            if let Ok(mut status) = status.lock() {
+                status.recording = true;
+                status.output_path = output_path.clone();
+            }
+            events.started(Utc::now(), output_path);
+        }
+        Some("OBS_WEBSOCKET_OUTPUT_STOPPED") => {
+            if let Ok(mut status) = status.lock() {
+                status.recording = false;
+                if output_path.is_some() {
+                    status.output_path = output_path.clone();
+                }
+            }
+            events.stopped(output_path);
+        }
+        _ => {}
+    }
+}
+
+fn on_record_status(events: &impl RecordingEvents, status: &Arc<Mutex<ObsStatus>>, data: &Value) {
+    if !data["outputActive"].as_bool().unwrap_or(false) {
+        return;
+    }
+
+    // Re-anchor on every poll so pauses in OBS are reflected in marker timecodes
+    let duration_ms = data["outputDuration"].as_i64().unwrap_or(0);
+    let started_at = Utc::now() - ChronoDuration::milliseconds(duration_ms);
+
+    let (was_recording, output_path) = {
+        let mut status = match status.lock() {
+            Ok(status) => status,
+            Err(_) => return,
+        };
+        let was_recording = std::mem::replace(&mut status.recording, true);
+        (was_recording, status.output_path.clone())
+    };
+
+    // Only a recording we haven't seen yet may start a session; a session the
+    // user stopped by hand mid-recording stays stopped.
+    if was_recording {
+        events.reanchored(started_at);
+    } else {
+        events.started(started_at, output_path);
+    }
+}
+
+/// Anchors the active session to the OBS recording, starting a session first if
+/// none is running.
+fn start_recording_session(
+    app_handle: &AppHandle,
+    started_at: DateTime<Utc>,
+    output_path: Option<String>,
+) {
+    let state = app_handle.state::<AppState>();
+    let mut active_session = state.active_session.lock().unwrap();
+
+    match active_session.as_mut() {
+        Some(active) => {
+            active.recording_started_at = Some(started_at);
+            if output_path.is_some() {
+                active.recording_path = output_path;
+            }
+        }
+        None => {
+            let mut new_session = ActiveSession::new();
+            new_session.recording_started_at = Some(started_at);
+            new_session.recording_path = output_path;
+            hotkey::start_session(app_handle, &mut active_session, new_session);
+        }
+    }
+}
+
+fn reanchor_recording_session(app_handle: &AppHandle, started_at: DateTime<Utc>) {
+    let state = app_handle.state::<AppState>();
+    let mut active_session = state.active_session.lock().unwrap();
+
+    if let Some(active) = active_session.as_mut() {
+        active.recording_started_at = Some(started_at);
+    }
+}
+
+fn stop_recording_session(app_handle: &AppHandle, output_path: Option<String>) {
+    let state = app_handle.state::<AppState>();
+    let mut active_session = state.active_session.lock().unwrap();
+
+    if let Some(active) = active_session.as_mut() {
+        if output_path.is_some() {
+            active.recording_path = output_path;
+        }
+    }
+
+    hotkey::stop_session(app_handle, &mut active_session, &state.storage);
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn get_obs_status(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<ObsStatus, String> {
+    let obs = app_state.obs.lock().map_err(|e| e.to_string())?;
+    Ok(obs.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const PASSWORD: &str = "supersecret";
+    const SALT: &str = "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=";
+    const CHALLENGE: &str = "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY=";
+    const RECORDED_MS: i64 = 12_000;
+    const OUTPUT_PATH: &str = "/recordings/2026-01-01 10-00-00.mkv";
+
+    /// Keeps the session the connection would have started in the app, and
+    /// those it stopped.
+    #[derive(Default)]
+    struct Recorder {
+        session: Mutex<Option<ActiveSession>>,
+        stopped: Mutex<Vec<ActiveSession>>,
+    }
+
+    impl RecordingEvents for Recorder {
+        fn started(&self, started_at: DateTime<Utc>, output_path: Option<String>) {
+            let mut session = self.session.lock().unwrap();
+            let session = session.get_or_insert_with(ActiveSession::new);
+            session.recording_started_at = Some(started_at);
+            session.recording_path = output_path;
+        }
+
+        fn reanchored(&self, started_at: DateTime<Utc>) {
+            if let Some(session) = self.session.lock().unwrap().as_mut() {
+                session.recording_started_at = Some(started_at);
+            }
+        }
+
+        fn stopped(&self, output_path: Option<String>) {
+            if let Some(mut session) = self.session.lock().unwrap().take() {
+                if output_path.is_some() {
+                    session.recording_path = output_path;
+                }
+                self.stopped.lock().unwrap().push(session);
+            }
+        }
+    }
+
+    type ServerSocket = WebSocketStream<TcpStream>;
+
+    async fn send(socket: &mut ServerSocket, op: u64, d: Value) -> Result<()> {
+        let message = json!({ "op": op, "d": d });
+        socket.send(Message::Text(message.to_string().into())).await?;
+        Ok(())
+    }
+
+    async fn receive(socket: &mut ServerSocket) -> Result<Value> {
+        loop {
+            match socket.next().await {
+                Some(message) => {
+                    if let Message::Text(text) = message? {
+                        return Ok(serde_json::from_str(&text)?);
+                    }
+                }
+                None => bail!("Client closed the connection"),
+            }
+        }
+    }
+
+    async fn close(mut socket: ServerSocket) -> Result<()> {
+        socket.close(None).await?;
+        while socket.next().await.is_some() {}
+        Ok(())
+    }
+
+    fn record_status(active: bool) -> Value {
+        json!({
+            "outputActive": active,
+            "outputPaused": false,
+            "outputTimecode": format_timecode(if active { RECORDED_MS } else { 0 }),
+            "outputDuration": if active { RECORDED_MS } else { 0 },
+            "outputBytes": if active { 1_048_576 } else { 0 },
+        })
+    }
+
+    fn record_state_changed(state: &str, output_path: Option<&str>) -> Value {
+        json!({
+            "eventType": "RecordStateChanged",
+            "eventIntent": 64,
+            "eventData": {
+                "outputActive": state == "OBS_WEBSOCKET_OUTPUT_STARTED",
+                "outputState": state,
+                "outputPath": output_path,
+            },
+        })
+    }
+
+    /// Serves one connection the way obs-websocket does: a Hello with an
+    /// authentication challenge, Identify, then a `GetRecordStatus` answered
+    /// with `status`, followed by `events`.
+    async fn serve_once(listener: TcpListener, status: Value, events: Vec<Value>) -> Result<()> {
+        let (stream, _) = listener.accept().await?;
+        let mut socket = tokio_tungstenite::accept_async(stream).await?;
+
+        send(
+            &mut socket,
+            OP_HELLO,
+            json!({
+                "obsWebSocketVersion": "5.5.0",
+                "rpcVersion": RPC_VERSION,
+                "authentication": { "challenge": CHALLENGE, "salt": SALT },
+            }),
+        )
+        .await?;
+
+        let identify = receive(&mut socket).await?;
+        assert_eq!(identify["op"], OP_IDENTIFY);
+        assert_eq!(identify["d"]["rpcVersion"], RPC_VERSION);
+        if identify["d"]["authentication"] != authentication_string(PASSWORD, SALT, CHALLENGE) {
+            return close(socket).await;
+        }
+        send(
+            &mut socket,
+            OP_IDENTIFIED,
+            json!({ "negotiatedRpcVersion": RPC_VERSION }),
+        )
+        .await?;
+
+        let request = receive(&mut socket).await?;
+        assert_eq!(request["op"], OP_REQUEST);
+        assert_eq!(request["d"]["requestType"], "GetRecordStatus");
+        send(
+            &mut socket,
+            OP_REQUEST_RESPONSE,
+            json!({
+                "requestType": "GetRecordStatus",
+                "requestId": request["d"]["requestId"],
+                "requestStatus": { "result": true, "code": 100 },
+                "responseData": status,
+            }),
+        )
+        .await?;
+
+        for event in events {
+            send(&mut socket, OP_EVENT, event).await?;
+        }
+
+        close(socket).await
+    }
+
+    async fn connect(
+        password: &str,
+        status: Value,
+        events: Vec<Value>,
+    ) -> (Result<()>, Recorder, ObsStatus) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let settings = ObsSettings {
+            enabled: true,
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            password: Some(password.to_string()),
+        };
+        let server = tokio::spawn(serve_once(listener, status, events));
+
+        let recorder = Recorder::default();
+        let status = Arc::new(Mutex::new(ObsStatus::default()));
+        let result = run_connection(&recorder, &settings, &status).await;
+        server.await.unwrap().unwrap();
+
+        let status = status.lock().unwrap().clone();
+        (result, recorder, status)
+    }
+
+    #[tokio::test]
+    async fn running_recording_sets_marker_offsets() {
+        let (result, recorder, status) = connect(PASSWORD, record_status(true), Vec::new()).await;
+
+        result.unwrap();
+        assert!(status.connected);
+        assert!(status.recording);
+
+        let session = recorder.session.lock().unwrap();
+        let session = session.as_ref().expect("no session was started");
+        let offset_ms = session
+            .new_marker("Intro")
+            .recording_offset_ms
+            .expect("marker has no recording offset");
+        assert!(
+            (RECORDED_MS..RECORDED_MS + 5_000).contains(&offset_ms),
+            "unexpected recording offset {}",
+            offset_ms
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_password_fails_the_handshake() {
+        let (result, recorder, status) = connect("wrong", record_status(true), Vec::new()).await;
+
+        assert!(result.is_err());
+        assert!(!status.connected);
+        assert!(recorder.session.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn recording_starts_and_stops_a_session() {
+        let events = vec![
+            record_state_changed("OBS_WEBSOCKET_OUTPUT_STARTING", None),
+            record_state_changed("OBS_WEBSOCKET_OUTPUT_STARTED", Some(OUTPUT_PATH)),
+            record_state_changed("OBS_WEBSOCKET_OUTPUT_STOPPING", None),
+            record_state_changed("OBS_WEBSOCKET_OUTPUT_STOPPED", Some(OUTPUT_PATH)),
+        ];
+        let (result, recorder, status) = connect(PASSWORD, record_status(false), events).await;
+
+        result.unwrap();
+        assert!(!status.recording);
+        assert_eq!(status.output_path.as_deref(), Some(OUTPUT_PATH));
+        assert!(recorder.session.lock().unwrap().is_none());
+
+        let stopped = recorder.stopped.lock().unwrap();
+        let [session] = &stopped[..] else {
+            panic!("expected one stopped session, got {}", stopped.len());
+        };
+        assert_eq!(session.recording_path.as_deref(), Some(OUTPUT_PATH));
+        let offset_ms = session
+            .new_marker("Outro")
+            .recording_offset_ms
+            .expect("marker has no recording offset");
+        assert!(
+            (0..5_000).contains(&offset_ms),
+            "unexpected recording offset {}",
+            offset_ms
+        );
+    }
+}
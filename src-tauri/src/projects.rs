@@ -0,0 +1,347 @@
+//! Projects that group sessions, each with its own sessions directory.
+//!
+//! The built-in default project keeps its sessions directly in the data
+//! directory, where they were stored before projects existed. Every other
+//! project stores them under `projects/<id>/`. The project list and the active
+//! project are kept in `projects.json`.
+
+use crate::{
+    session::Session,
+    storage::{self, ExportFormat, Storage},
+    AppState,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+use tauri::{AppHandle, Emitter as _, Manager};
+use uuid::Uuid;
+
+pub const DEFAULT_PROJECT_ID: &str = "default";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectList {
+    pub projects: Vec<Project>,
+    pub active: String,
+}
+
+impl Default for ProjectList {
+    fn default() -> Self {
+        Self {
+            projects: vec![Project {
+                id: DEFAULT_PROJECT_ID.to_string(),
+                name: "Default".to_string(),
+                created: Utc::now(),
+            }],
+            active: DEFAULT_PROJECT_ID.to_string(),
+        }
+    }
+}
+
+impl ProjectList {
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join("projects.json")
+    }
+
+    /// Loads the project list from `data_dir`, with just the default project if
+    /// none was saved yet.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let file_path = Self::path(data_dir);
+
+        if !file_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let file = File::open(&file_path)
+            .with_context(|| format!("Failed to open file at {:?}", file_path))?;
+
+        let reader = BufReader::new(file);
+        let mut list: Self = serde_json::from_reader(reader)
+            .with_context(|| format!("Failed to deserialize projects from {:?}", file_path))?;
+
+        if list.find(&list.active).is_none() {
+            list.active = DEFAULT_PROJECT_ID.to_string();
+        }
+
+        Ok(list)
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        storage::write_json_atomic(&Self::path(data_dir), self)
+    }
+
+    pub fn find(&self, id: &str) -> Option<&Project> {
+        self.projects.iter().find(|project| project.id == id)
+    }
+
+    fn get(&self, id: &str) -> Result<&Project> {
+        match self.find(id) {
+            Some(project) => Ok(project),
+            None => bail!("Project with ID {} not found", id),
+        }
+    }
+}
+
+/// Directory holding the sessions of the project with `id`.
+pub fn sessions_dir(data_dir: &Path, id: &str) -> PathBuf {
+    if id == DEFAULT_PROJECT_ID {
+        data_dir.to_path_buf()
+    } else {
+        data_dir.join("projects").join(id)
+    }
+}
+
+/// Sessions of the project with `id`, newest first.
+fn project_sessions(storage: &Storage, id: &str) -> Result<Vec<Session>> {
+    storage::list_sessions_in(&sessions_dir(storage.data_dir(), id))
+}
+
+/// Whether `dir` or any folder below it holds a file, e.g. an archived,
+/// trashed or unreadable session.
+fn contains_files(dir: &Path) -> Result<bool> {
+    if !dir.exists() {
+        return Ok(false);
+    }
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read directory {:?}", dir))? {
+        let entry = entry?;
+        let is_dir = entry.file_type()?.is_dir();
+        if !is_dir || contains_files(&entry.path())? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Makes the project with `id` active. Fails while a session is running,
+/// since it would be saved to the new project.
+pub fn switch_to(app_handle: &AppHandle, id: &str) -> Result<()> {
+    let state = app_handle.state::<AppState>();
+
+    let undo_state = {
+        let active_session = state.active_session.lock().unwrap();
+        if active_session.is_some() {
+            bail!("Stop the current session before switching projects");
+        }
+
+        let mut storage = state.storage.lock().unwrap();
+        let mut list = state.projects.lock().unwrap();
+        list.get(id)?;
+
+        let dir = sessions_dir(storage.data_dir(), id);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create project directory at {:?}", dir))?;
+
+        list.active = id.to_string();
+        list.save(storage.data_dir())?;
+        storage.set_sessions_dir(dir);
+
+        // Recorded changes refer to sessions of the previous project
+        let mut undo = state.undo.lock().unwrap();
+        undo.clear();
+        undo.state()
+    };
+
+    app_handle
+        .emit("project-changed", id)
+        .unwrap_or_else(|e| log::error!("Failed to emit project-changed event: {:?}", e));
+    crate::undo::emit_state(app_handle, &undo_state);
+    crate::tray::refresh_tray_menu(app_handle);
+
+    Ok(())
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn list_projects(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<ProjectList, String> {
+    let list = app_state.projects.lock().map_err(|e| e.to_string())?;
+    Ok(list.clone())
+}
+
+#[tauri::command]
+pub async fn create_project(
+    name: String,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Project, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let mut list = app_state.projects.lock().map_err(|e| e.to_string())?;
+
+    let project = Project {
+        id: Uuid::new_v4().to_string(),
+        name,
+        created: Utc::now(),
+    };
+    let dir = sessions_dir(storage.data_dir(), &project.id);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    list.projects.push(project.clone());
+    list.save(storage.data_dir()).map_err(|e| e.to_string())?;
+
+    drop((storage, list));
+    crate::tray::refresh_tray_menu(&handle);
+
+    Ok(project)
+}
+
+#[tauri::command]
+pub async fn rename_project(
+    id: String,
+    name: String,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Project, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let mut list = app_state.projects.lock().map_err(|e| e.to_string())?;
+
+    let project = list
+        .projects
+        .iter_mut()
+        .find(|project| project.id == id)
+        .ok_or_else(|| format!("Project with ID {} not found", id))?;
+    project.name = name;
+    let project = project.clone();
+
+    list.save(storage.data_dir()).map_err(|e| e.to_string())?;
+
+    drop((storage, list));
+    crate::tray::refresh_tray_menu(&handle);
+
+    Ok(project)
+}
+
+/// Deletes an empty project. The default and the active project can't be deleted.
+/// The project folder must not hold any file, including archived and trashed
+/// sessions, so nothing is lost by removing it.
+#[tauri::command]
+pub async fn delete_project(
+    id: String,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let mut list = app_state.projects.lock().map_err(|e| e.to_string())?;
+
+    if id == DEFAULT_PROJECT_ID || id == list.active {
+        return Err("The default and the active project can't be deleted".to_string());
+    }
+    list.get(&id).map_err(|e| e.to_string())?;
+
+    let dir = sessions_dir(storage.data_dir(), &id);
+    if contains_files(&dir).map_err(|e| e.to_string())? {
+        return Err(
+            "Move or delete the sessions of this project first, including archived and trashed ones"
+                .to_string(),
+        );
+    }
+
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| e.to_string())?;
+    }
+
+    list.projects.retain(|project| project.id != id);
+    list.save(storage.data_dir()).map_err(|e| e.to_string())?;
+
+    drop((storage, list));
+    crate::tray::refresh_tray_menu(&handle);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn switch_project(id: String, handle: tauri::AppHandle) -> Result<(), String> {
+    switch_to(&handle, &id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_project_sessions(
+    id: String,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<Session>, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let list = app_state.projects.lock().map_err(|e| e.to_string())?;
+    list.get(&id).map_err(|e| e.to_string())?;
+
+    project_sessions(&storage, &id).map_err(|e| e.to_string())
+}
+
+/// Moves a stored session from the active project into the project with `project_id`.
+#[tauri::command]
+pub async fn move_session_to_project(
+    session_id: String,
+    project_id: String,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let list = app_state.projects.lock().map_err(|e| e.to_string())?;
+    list.get(&project_id).map_err(|e| e.to_string())?;
+
+    let session = storage
+        .get_session(&session_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session with ID {} not found", session_id))?;
+
+    let target = sessions_dir(storage.data_dir(), &project_id);
+    if target == storage.sessions_dir() {
+        return Ok(());
+    }
+
+    storage
+        .save_session_to(&target, &session)
+        .map_err(|e| e.to_string())?;
+    storage.delete_session(&session_id).map_err(|e| e.to_string())
+}
+
+/// Exports every session of a project into a folder picked by the user, one
+/// file per session. Returns the number of sessions exported.
+#[tauri::command]
+pub async fn export_project(
+    id: String,
+    format: ExportFormat,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<usize, String> {
+    let default_rate = app_state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .default_frame_rate;
+
+    let sessions = {
+        let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+        let list = app_state.projects.lock().map_err(|e| e.to_string())?;
+        list.get(&id).map_err(|e| e.to_string())?;
+        project_sessions(&storage, &id).map_err(|e| e.to_string())?
+    };
+
+    use tauri_plugin_dialog::DialogExt;
+
+    let folder = handle
+        .dialog()
+        .file()
+        .set_title("Export Project")
+        .blocking_pick_folder()
+        .ok_or_else(|| "No folder selected".to_string())?;
+    let folder = folder.as_path().ok_or_else(|| "Invalid folder".to_string())?;
+
+    for session in &sessions {
+        let path = folder.join(format!("session_{}.{}", session.id, format.extension()));
+        storage::export_to(session, &path, format, default_rate).map_err(|e| e.to_string())?;
+    }
+
+    Ok(sessions.len())
+}
@@ -0,0 +1,199 @@
+//! Optional watcher that starts and stops sessions from a recordings folder.
+//!
+//! The folder is polled for media files. When one grows between two polls a
+//! session is started with that file as its recording, and once it has stopped
+//! growing for `idle_secs` the session is stopped, which attaches the file.
+//! If a session was already running, the file is linked to it instead and the
+//! session is left for the user to stop.
+
+use crate::{hotkey, session::ActiveSession, AppState};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tauri::{async_runtime::JoinHandle, AppHandle, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingWatcherSettings {
+    pub enabled: bool,
+    pub directory: Option<PathBuf>,
+    /// File extensions treated as recordings, without the leading dot.
+    pub extensions: Vec<String>,
+    /// How long a file must stop growing before its session is stopped.
+    pub idle_secs: u64,
+}
+
+impl Default for RecordingWatcherSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            extensions: ["mkv", "mp4", "mov", "flv", "ts"]
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            idle_secs: 10,
+        }
+    }
+}
+
+/// Owns the background polling task.
+#[derive(Default)]
+pub struct RecordingWatcher {
+    task: Option<JoinHandle<()>>,
+}
+
+impl RecordingWatcher {
+    /// Stops any running watcher and starts a new one with `settings` if enabled.
+    pub fn restart(&mut self, app_handle: &AppHandle, settings: &RecordingWatcherSettings) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+
+        if !settings.enabled {
+            return;
+        }
+
+        match &settings.directory {
+            Some(directory) => {
+                log::info!("Watching {:?} for recordings", directory);
+                self.task = Some(tauri::async_runtime::spawn(watch(
+                    app_handle.clone(),
+                    settings.clone(),
+                )));
+            }
+            None => log::warn!("Recording watcher is enabled but no directory is set"),
+        }
+    }
+}
+
+struct Recording {
+    path: PathBuf,
+    /// The session the watcher started, if it started one.
+    session_id: Option<String>,
+    last_growth: Instant,
+}
+
+async fn watch(app_handle: AppHandle, settings: RecordingWatcherSettings) {
+    let Some(directory) = settings.directory.clone() else {
+        return;
+    };
+    let idle = Duration::from_secs(settings.idle_secs);
+
+    // Files already present only count once they grow
+    let mut sizes = scan(&directory, &settings.extensions).unwrap_or_default();
+    let mut recording: Option<Recording> = None;
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        poll.tick().await;
+
+        let current = match scan(&directory, &settings.extensions) {
+            Ok(current) => current,
+            Err(err) => {
+                log::warn!("Failed to scan recordings folder: {:?}", err);
+                continue;
+            }
+        };
+        let now = Instant::now();
+
+        for (path, size) in &current {
+            let grew = sizes.get(path).is_some_and(|previous| size > previous);
+            if !grew {
+                continue;
+            }
+
+            match &mut recording {
+                Some(recording) if &recording.path == path => recording.last_growth = now,
+                Some(_) => {}
+                None => {
+                    log::info!("Recording detected: {:?}", path);
+                    recording = Some(Recording {
+                        path: path.clone(),
+                        session_id: start_recording(&app_handle, path),
+                        last_growth: now,
+                    });
+                }
+            }
+        }
+
+        sizes = current;
+
+        if let Some(finished) = recording.take_if(|recording| now - recording.last_growth >= idle) {
+            log::info!("Recording finished: {:?}", finished.path);
+            stop_recording(&app_handle, &finished);
+        }
+    }
+}
+
+fn scan(directory: &Path, extensions: &[String]) -> Result<HashMap<PathBuf, u64>> {
+    let mut sizes = HashMap::new();
+
+    for entry in fs::read_dir(directory)
+        .with_context(|| format!("Failed to read directory {:?}", directory))?
+    {
+        let entry = entry?;
+        let path = entry.path();
+
+        let is_recording = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(ext))
+            });
+
+        if is_recording {
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                sizes.insert(path, metadata.len());
+            }
+        }
+    }
+
+    Ok(sizes)
+}
+
+/// Starts a session for the recording at `path`, or links it to the running
+/// session. Returns the ID of the session if the watcher started it.
+fn start_recording(app_handle: &AppHandle, path: &Path) -> Option<String> {
+    let state = app_handle.state::<AppState>();
+    let mut active_session = state.active_session.lock().unwrap();
+    let recording_path = path.to_string_lossy().into_owned();
+
+    match active_session.as_mut() {
+        Some(active) => {
+            if active.recording_path.is_none() {
+                active.recording_path = Some(recording_path);
+            }
+            None
+        }
+        None => {
+            let mut new_session = ActiveSession::new();
+            new_session.recording_path = Some(recording_path);
+            let id = new_session.id.clone();
+            hotkey::start_session(app_handle, &mut active_session, new_session);
+            Some(id)
+        }
+    }
+}
+
+fn stop_recording(app_handle: &AppHandle, recording: &Recording) {
+    let Some(session_id) = &recording.session_id else {
+        return;
+    };
+
+    let state = app_handle.state::<AppState>();
+    let mut active_session = state.active_session.lock().unwrap();
+
+    // Leave it alone if the user already stopped it and started another
+    if active_session.as_ref().map(|active| &active.id) == Some(session_id) {
+        hotkey::stop_session(app_handle, &mut active_session, &state.storage);
+    }
+}
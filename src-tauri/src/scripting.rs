@@ -0,0 +1,468 @@
+//! User scripts (Rhai) that run on marker and session events.
+//!
+//! Every `*.rhai` file in the `scripts/` folder of the data directory is
+//! compiled at startup and on `reload_scripts`, then run in file name order.
+//! Scripts may define:
+//!
+//! - `fn on_marker(marker, session)`: runs before a marker is committed.
+//!   Return a map with `label` and/or `notes` to change the marker, `false` to
+//!   drop it, or anything else to keep it as is.
+//! - `fn on_session_stop(session)`: runs before a stopped session is saved.
+//!   Return a map with `name` to name the session.
+//!
+//! Markers and sessions are passed as plain maps, so scripts can't reach
+//! anything but the data they are given; `eval` and `import` are disabled.
+//! Execution is bounded by operation, call depth and size limits, and
+//! `print`/`debug` go to the application log.
+
+use crate::session::{ActiveSession, Marker, Session};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use rhai::{
+    module_resolvers::DummyModuleResolver, Array, CallFnOptions, Dynamic, Engine, Map, Scope,
+    AST,
+};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_STRING_SIZE: usize = 64 * 1024;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+struct Script {
+    name: String,
+    ast: AST,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptInfo {
+    pub name: String,
+    pub error: Option<String>,
+}
+
+pub struct ScriptEngine {
+    dir: PathBuf,
+    engine: Engine,
+    scripts: Vec<Script>,
+    info: Vec<ScriptInfo>,
+}
+
+impl ScriptEngine {
+    pub fn new(data_dir: &Path) -> Self {
+        let mut engine = Engine::new();
+
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE)
+            .disable_symbol("eval")
+            .set_module_resolver(DummyModuleResolver::new());
+
+        engine.on_print(|text| log::info!("[script] {}", text));
+        engine.on_debug(|text, source, pos| {
+            log::debug!("[script {}:{}] {}", source.unwrap_or("?"), pos, text)
+        });
+
+        let mut script_engine = Self {
+            dir: data_dir.join("scripts"),
+            engine,
+            scripts: Vec::new(),
+            info: Vec::new(),
+        };
+
+        if let Err(err) = script_engine.reload() {
+            log::error!("Failed to load scripts: {:?}", err);
+        }
+
+        script_engine
+    }
+
+    /// Recompiles every script in the scripts folder.
+    pub fn reload(&mut self) -> Result<Vec<ScriptInfo>> {
+        self.scripts.clear();
+        self.info.clear();
+
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths: Vec<PathBuf> = fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read scripts from {:?}", self.dir))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("rhai"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default()
+                .to_string();
+
+            match self.compile(&path) {
+                Ok(ast) => {
+                    log::info!("Loaded script {}", name);
+                    self.scripts.push(Script {
+                        name: name.clone(),
+                        ast,
+                    });
+                    self.info.push(ScriptInfo { name, error: None });
+                }
+                Err(err) => {
+                    log::error!("Failed to compile script {}: {:?}", name, err);
+                    self.info.push(ScriptInfo {
+                        name,
+                        error: Some(format!("{:#}", err)),
+                    });
+                }
+            }
+        }
+
+        Ok(self.info.clone())
+    }
+
+    fn compile(&self, path: &Path) -> Result<AST> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script at {:?}", path))?;
+        let mut ast = self
+            .engine
+            .compile(source)
+            .with_context(|| format!("Failed to compile script at {:?}", path))?;
+        ast.set_source(path.to_string_lossy().as_ref());
+        Ok(ast)
+    }
+
+    pub fn scripts(&self) -> &[ScriptInfo] {
+        &self.info
+    }
+
+    /// Runs `on_marker` hooks over a marker about to be added to `session`.
+    /// Returns `None` if a script dropped it.
+    pub fn process_marker(&self, session: &ActiveSession, mut marker: Marker) -> Option<Marker> {
+        for script in self.scripts_defining("on_marker", 2) {
+            let marker_arg = marker_map(&marker, session.start_time, session.markers.last());
+            let session_arg = session_map(&session.id, session.start_time, &session.markers, None);
+
+            match self.call(script, "on_marker", (marker_arg, session_arg)) {
+                Ok(result) => {
+                    if result.as_bool() == Ok(false) {
+                        log::info!("Script {} dropped marker {}", script.name, marker.id);
+                        return None;
+                    }
+
+                    if let Some(changes) = result.try_cast::<Map>() {
+                        if let Some(label) = string_field(&changes, "label") {
+                            marker.label = label;
+                        }
+                        if let Some(notes) = changes.get("notes") {
+                            marker.notes = notes.clone().into_string().ok();
+                        }
+                    }
+                }
+                Err(err) => log::error!("Script {} failed in on_marker: {}", script.name, err),
+            }
+        }
+
+        Some(marker)
+    }
+
+    /// Runs `on_session_stop` hooks over a session about to be saved.
+    pub fn process_session_stop(&self, session: &mut Session) {
+        for script in self.scripts_defining("on_session_stop", 1) {
+            let session_arg = session_map(
+                &session.id,
+                session.start_time,
+                &session.markers,
+                Some(session),
+            );
+
+            match self.call(script, "on_session_stop", (session_arg,)) {
+                Ok(result) => {
+                    if let Some(changes) = result.try_cast::<Map>() {
+                        if let Some(name) = string_field(&changes, "name") {
+                            session.name = Some(name);
+                        }
+                    }
+                }
+                Err(err) => log::error!("Script {} failed in on_session_stop: {}", script.name, err),
+            }
+        }
+    }
+
+    fn scripts_defining<'a>(
+        &'a self,
+        name: &'a str,
+        params: usize,
+    ) -> impl Iterator<Item = &'a Script> + 'a {
+        self.scripts.iter().filter(move |script| {
+            script
+                .ast
+                .iter_functions()
+                .any(|function| function.name == name && function.params.len() == params)
+        })
+    }
+
+    fn call(
+        &self,
+        script: &Script,
+        name: &str,
+        args: impl rhai::FuncArgs,
+    ) -> Result<Dynamic, Box<rhai::EvalAltResult>> {
+        // Only the hook function runs, not the script's top-level statements
+        let options = CallFnOptions::new().eval_ast(false);
+        self.engine
+            .call_fn_with_options(options, &mut Scope::new(), &script.ast, name, args)
+    }
+}
+
+fn string_field(map: &Map, key: &str) -> Option<String> {
+    map.get(key).and_then(|value| value.clone().into_string().ok())
+}
+
+fn optional_string(value: Option<&str>) -> Dynamic {
+    value.map_or(Dynamic::UNIT, |value| value.to_string().into())
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 1000.0
+}
+
+fn marker_map(marker: &Marker, session_start: DateTime<Utc>, previous: Option<&Marker>) -> Map {
+    let mut map = Map::new();
+    map.insert("id".into(), marker.id.clone().into());
+    map.insert("label".into(), marker.label.clone().into());
+    map.insert("notes".into(), optional_string(marker.notes.as_deref()));
+    map.insert("category".into(), optional_string(marker.category.as_deref()));
+    map.insert("timestamp".into(), marker.timestamp.to_rfc3339().into());
+    let offset = marker.offset_from(session_start);
+    map.insert("offset_secs".into(), seconds(offset).into());
+    map.insert(
+        "since_last_secs".into(),
+        previous.map_or(Dynamic::UNIT, |previous| {
+            seconds(offset - previous.offset_from(session_start)).into()
+        }),
+    );
+    map
+}
+
+fn session_map(
+    id: &str,
+    start_time: DateTime<Utc>,
+    markers: &[Marker],
+    completed: Option<&Session>,
+) -> Map {
+    let mut map = Map::new();
+    map.insert("id".into(), id.to_string().into());
+    map.insert("start_time".into(), start_time.to_rfc3339().into());
+
+    if let Some(session) = completed {
+        map.insert("end_time".into(), session.end_time.to_rfc3339().into());
+        map.insert(
+            "duration_secs".into(),
+            seconds(session.duration()).into(),
+        );
+        map.insert("name".into(), optional_string(session.name.as_deref()));
+        map.insert(
+            "tags".into(),
+            session
+                .tags
+                .iter()
+                .map(|tag| Dynamic::from(tag.clone()))
+                .collect::<Array>()
+                .into(),
+        );
+    }
+
+    let markers: Array = markers
+        .iter()
+        .enumerate()
+        .map(|(index, marker)| {
+            let previous = index.checked_sub(1).map(|previous| &markers[previous]);
+            marker_map(marker, start_time, previous).into()
+        })
+        .collect();
+    map.insert("marker_count".into(), (markers.len() as i64).into());
+    map.insert("markers".into(), markers.into());
+    map
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn list_scripts(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<ScriptInfo>, String> {
+    let scripts = app_state.scripts.lock().map_err(|e| e.to_string())?;
+    Ok(scripts.scripts().to_vec())
+}
+
+#[tauri::command]
+pub async fn reload_scripts(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<ScriptInfo>, String> {
+    let mut scripts = app_state.scripts.lock().map_err(|e| e.to_string())?;
+    scripts.reload().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use uuid::Uuid;
+
+    /// A script engine over a fresh data directory holding `scripts`.
+    fn engine(scripts: &[(&str, &str)]) -> (ScriptEngine, PathBuf) {
+        let data_dir =
+            std::env::temp_dir().join(format!("markermoment-scripts-{}", Uuid::new_v4()));
+        fs::create_dir_all(data_dir.join("scripts")).unwrap();
+        for (name, source) in scripts {
+            fs::write(data_dir.join("scripts").join(name), source).unwrap();
+        }
+
+        let engine = ScriptEngine::new(&data_dir);
+        assert!(
+            engine.scripts().iter().all(|script| script.error.is_none()),
+            "scripts failed to compile: {:?}",
+            engine.scripts()
+        );
+        (engine, data_dir)
+    }
+
+    /// Runs `on_marker` over a marker labelled `label` in a new session.
+    fn process(engine: &ScriptEngine, label: &str) -> Option<Marker> {
+        let session = ActiveSession::new();
+        engine.process_marker(&session, session.new_marker(label))
+    }
+
+    #[test]
+    fn on_marker_changes_or_drops_markers() {
+        let (engine, data_dir) = engine(&[(
+            "markers.rhai",
+            r#"
+                fn on_marker(marker, session) {
+                    if marker.label == "drop" {
+                        return false;
+                    }
+                    #{ label: marker.label + "!", notes: "after " + session.marker_count }
+                }
+            "#,
+        )]);
+
+        let marker = process(&engine, "Intro").unwrap();
+        assert_eq!(marker.label, "Intro!");
+        assert_eq!(marker.notes.as_deref(), Some("after 0"));
+        assert!(process(&engine, "drop").is_none());
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn scripts_run_in_file_name_order() {
+        let (engine, data_dir) = engine(&[
+            (
+                "b.rhai",
+                r#"fn on_marker(marker, session) { #{ label: marker.label + "b" } }"#,
+            ),
+            (
+                "a.rhai",
+                r#"fn on_marker(marker, session) { #{ label: marker.label + "a" } }"#,
+            ),
+        ]);
+
+        assert_eq!(process(&engine, "").unwrap().label, "ab");
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn on_session_stop_names_the_session() {
+        let (engine, data_dir) = engine(&[(
+            "sessions.rhai",
+            r#"
+                fn on_session_stop(session) {
+                    #{ name: "Episode with " + session.marker_count + " markers" }
+                }
+            "#,
+        )]);
+
+        let mut active = ActiveSession::new();
+        let marker = active.new_marker("Intro");
+        active.push_marker(marker);
+        let mut session = active.to_session();
+        engine.process_session_stop(&mut session);
+
+        assert_eq!(session.name.as_deref(), Some("Episode with 1 markers"));
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn runaway_script_is_stopped() {
+        let (engine, data_dir) = engine(&[(
+            "loop.rhai",
+            "fn on_marker(marker, session) { loop {} }",
+        )]);
+
+        let started = Instant::now();
+        let marker = process(&engine, "Intro").unwrap();
+
+        // A failing script leaves the marker as it was
+        assert_eq!(marker.label, "Intro");
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_string_is_refused() {
+        let (engine, data_dir) = engine(&[(
+            "grow.rhai",
+            r#"
+                fn on_marker(marker, session) {
+                    let label = "x";
+                    while label.len() < 1000000 {
+                        label += label;
+                    }
+                    #{ label: label }
+                }
+            "#,
+        )]);
+
+        assert_eq!(process(&engine, "Intro").unwrap().label, "Intro");
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn scripts_cannot_import_files() {
+        let data_dir =
+            std::env::temp_dir().join(format!("markermoment-scripts-{}", Uuid::new_v4()));
+        fs::create_dir_all(&data_dir).unwrap();
+        let module = data_dir.join("outside.rhai");
+        fs::write(&module, r#"fn label() { "from disk" }"#).unwrap();
+
+        let source = format!(
+            r#"
+                fn on_marker(marker, session) {{
+                    import {:?} as outside;
+                    #{{ label: outside::label() }}
+                }}
+            "#,
+            module.to_string_lossy()
+        );
+        let (engine, scripts_dir) = engine(&[("import.rhai", &source)]);
+
+        assert_eq!(process(&engine, "Intro").unwrap().label, "Intro");
+
+        fs::remove_dir_all(data_dir).unwrap();
+        fs::remove_dir_all(scripts_dir).unwrap();
+    }
+}
@@ -1,22 +1,207 @@
-use chrono::{DateTime, Utc};
+use crate::events::{self, AppEvent};
+use crate::media::MediaFile;
+use crate::templates;
+use crate::undo::{self, Change};
+use crate::timecode::FrameRate;
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter as _, Manager};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Marker {
     pub id: String,
     pub timestamp: DateTime<Utc>,
     pub label: String,
     pub notes: Option<String>,
+    /// Category from the session's template, e.g. `Highlight` or `Edit`.
+    pub category: Option<String>,
+    /// Offset into the linked OBS recording, if one was running.
+    pub recording_offset_ms: Option<i64>,
+    /// Time since the session started on the monotonic clock, immune to
+    /// wall-clock adjustments. Missing for markers recorded before it existed.
+    pub elapsed_ms: Option<u64>,
+    /// Length of a range marker, e.g. a highlight. Missing for point markers.
+    pub duration_ms: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Marker {
+    /// Time of this marker since `start_time`, preferring the monotonic elapsed time.
+    pub fn offset_from(&self, start_time: DateTime<Utc>) -> Duration {
+        match self.elapsed_ms {
+            Some(elapsed_ms) => Duration::milliseconds(elapsed_ms as i64),
+            None => self.timestamp - start_time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Session {
     pub id: String,
     pub start_time: DateTime<Utc>,
     pub end_time: DateTime<Utc>,
     pub markers: Vec<Marker>,
     pub name: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Output file of the OBS recording this session mirrored.
+    pub recording_path: Option<String>,
+    /// Media files recorded during this session.
+    #[serde(default)]
+    pub media: Vec<MediaFile>,
+    /// How far the recording started after the session, for media time.
+    pub media_offset: Option<MediaOffset>,
+    /// Frame rate for timecodes, if different from the default in settings.
+    pub frame_rate: Option<FrameRate>,
+    /// Timecode of the first frame of the recording, e.g. `01:00:00:00`.
+    pub start_timecode: Option<String>,
+    /// Length of the session on the monotonic clock.
+    pub elapsed_ms: Option<u64>,
+    /// Template the session was started from.
+    pub template_id: Option<String>,
+    /// Archived sessions this one was split or merged from.
+    #[serde(default)]
+    pub derived_from: Vec<String>,
+    /// When the session was moved to the trash.
+    pub trashed_at: Option<DateTime<Utc>>,
+}
+
+/// How a session's media offset was determined.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OffsetSource {
+    /// Entered by the user.
+    Manual { offset_ms: i64 },
+    /// The creation time of an attached media file.
+    MediaCreated { path: PathBuf },
+    /// A marker the user located in the recording at `media_time_ms`.
+    SyncMarker { marker_id: String, media_time_ms: i64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaOffset {
+    /// Recording start minus session start; positive if the recording started later.
+    pub offset_ms: i64,
+    pub source: OffsetSource,
+}
+
+impl Session {
+    pub fn details(&self) -> SessionDetails {
+        SessionDetails {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+        }
+    }
+
+    pub fn set_details(&mut self, details: SessionDetails) {
+        let details = details.normalized();
+        self.name = details.name;
+        self.description = details.description;
+        self.tags = details.tags;
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag))
+    }
+
+    /// Length of the session, preferring the monotonic elapsed time.
+    pub fn duration(&self) -> Duration {
+        match self.elapsed_ms {
+            Some(elapsed_ms) => Duration::milliseconds(elapsed_ms as i64),
+            None => self.end_time - self.start_time,
+        }
+    }
+
+    /// Time of `marker` since the session started.
+    pub fn session_time(&self, marker: &Marker) -> Duration {
+        marker.offset_from(self.start_time)
+    }
+
+    /// Time of `marker` in the recording. OBS timecodes are used as is; other
+    /// markers are shifted by the media offset, if one is set.
+    pub fn media_time(&self, marker: &Marker) -> Duration {
+        if let Some(offset_ms) = marker.recording_offset_ms {
+            return Duration::milliseconds(offset_ms);
+        }
+
+        let offset_ms = self
+            .media_offset
+            .as_ref()
+            .map_or(0, |offset| offset.offset_ms);
+        self.session_time(marker) - Duration::milliseconds(offset_ms)
+    }
+
+    /// Time in the recording where `marker` ends; its start for point markers.
+    pub fn media_end_time(&self, marker: &Marker) -> Duration {
+        self.media_time(marker) + Duration::milliseconds(marker.duration_ms.unwrap_or(0) as i64)
+    }
+
+    /// Works out the media offset described by `source`.
+    pub fn compute_media_offset(&self, source: &OffsetSource) -> Result<i64, String> {
+        match source {
+            OffsetSource::Manual { offset_ms } => Ok(*offset_ms),
+            OffsetSource::MediaCreated { path } => {
+                let media = self
+                    .media
+                    .iter()
+                    .find(|media| &media.path == path)
+                    .ok_or_else(|| format!("{:?} is not attached to this session", path))?;
+                let created = media
+                    .created
+                    .ok_or_else(|| format!("The creation time of {:?} is unknown", path))?;
+                Ok((created - self.start_time).num_milliseconds())
+            }
+            OffsetSource::SyncMarker {
+                marker_id,
+                media_time_ms,
+            } => {
+                let marker = self
+                    .markers
+                    .iter()
+                    .find(|marker| &marker.id == marker_id)
+                    .ok_or_else(|| format!("Marker with ID {} not found", marker_id))?;
+                Ok(self.session_time(marker).num_milliseconds() - media_time_ms)
+            }
+        }
+    }
+}
+
+/// User supplied name, description and tags of a session.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionDetails {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl SessionDetails {
+    /// Trims everything, drops empty values and duplicate tags (ignoring case).
+    fn normalized(self) -> Self {
+        let non_empty = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tags {
+            let tag = tag.trim();
+            if !tag.is_empty() && !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+
+        Self {
+            name: non_empty(self.name),
+            description: non_empty(self.description),
+            tags,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +209,17 @@ pub struct ActiveSession {
     pub id: String,
     pub start_time: DateTime<Utc>,
     pub markers: Vec<Marker>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When the linked OBS recording started, adjusted for pauses.
+    pub recording_started_at: Option<DateTime<Utc>>,
+    pub recording_path: Option<String>,
+    pub template_id: Option<String>,
+    /// Monotonic start of the session, for marker and session durations.
+    #[serde(skip, default = "Instant::now")]
+    pub started: Instant,
 }
 
 impl ActiveSession {
@@ -32,17 +228,56 @@ impl ActiveSession {
             id: Uuid::new_v4().to_string(),
             start_time: Utc::now(),
             markers: Vec::new(),
+            name: None,
+            description: None,
+            tags: Vec::new(),
+            recording_started_at: None,
+            recording_path: None,
+            template_id: None,
+            started: Instant::now(),
         }
     }
 
+    pub fn details(&self) -> SessionDetails {
+        SessionDetails {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+        }
+    }
+
+    pub fn set_details(&mut self, details: SessionDetails) {
+        let details = details.normalized();
+        self.name = details.name;
+        self.description = details.description;
+        self.tags = details.tags;
+    }
+
     pub fn add_marker(&mut self, label: &str) -> &Marker {
-        let marker = Marker {
+        let marker = self.new_marker(label);
+        self.push_marker(marker)
+    }
+
+    /// Builds a marker stamped with the current time without adding it, so it
+    /// can be passed through scripts first.
+    pub fn new_marker(&self, label: &str) -> Marker {
+        let elapsed = self.started.elapsed();
+        let timestamp = Utc::now();
+        Marker {
             id: Uuid::new_v4().to_string(),
-            timestamp: Utc::now(),
+            timestamp,
             label: label.to_string(),
             notes: None,
-        };
+            category: None,
+            recording_offset_ms: self
+                .recording_started_at
+                .map(|started_at| (timestamp - started_at).num_milliseconds()),
+            elapsed_ms: Some(elapsed.as_millis() as u64),
+            duration_ms: None,
+        }
+    }
 
+    pub fn push_marker(&mut self, marker: Marker) -> &Marker {
         self.markers.push(marker);
         self.markers.last().unwrap()
     }
@@ -53,40 +288,213 @@ impl ActiveSession {
             start_time: self.start_time,
             end_time: Utc::now(),
             markers: self.markers.clone(),
-            name: None,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+            recording_path: self.recording_path.clone(),
+            media: self.recording_media(),
+            media_offset: None,
+            frame_rate: None,
+            start_timecode: None,
+            elapsed_ms: Some(self.started.elapsed().as_millis() as u64),
+            template_id: self.template_id.clone(),
+            derived_from: Vec::new(),
+            trashed_at: None,
+        }
+    }
+
+    /// The OBS recording, if any, as an attached media file.
+    fn recording_media(&self) -> Vec<MediaFile> {
+        let Some(path) = &self.recording_path else {
+            return Vec::new();
+        };
+
+        match MediaFile::from_path(Path::new(path), false) {
+            Ok(media) => vec![media],
+            Err(err) => {
+                log::warn!("Failed to attach recording {}: {:?}", path, err);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Inserts `marker` among `markers`, keeping them in time order.
+pub fn insert_marker(markers: &mut Vec<Marker>, marker: Marker) {
+    let index = markers.partition_point(|existing| existing.timestamp <= marker.timestamp);
+    markers.insert(index, marker);
+}
+
+/// Runs `f` on the markers of the session with `id`, whether it is the active
+/// session or a stored one, and saves stored sessions afterwards.
+pub fn update_markers<T>(
+    app_state: &crate::AppState,
+    id: &str,
+    f: impl FnOnce(&mut Vec<Marker>) -> Result<T, String>,
+) -> Result<T, String> {
+    {
+        let mut active_session = app_state.active_session.lock().map_err(|e| e.to_string())?;
+        if let Some(active) = active_session.as_mut().filter(|active| active.id == id) {
+            return f(&mut active.markers);
+        }
+    }
+
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let mut session = storage
+        .get_session(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session with ID {} not found", id))?;
+
+    let result = f(&mut session.markers)?;
+
+    storage.save_session(&session).map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// The active session if it has `id`, otherwise the stored one.
+pub fn find_session(app_state: &crate::AppState, id: &str) -> Result<Session, String> {
+    {
+        let active_session = app_state.active_session.lock().map_err(|e| e.to_string())?;
+        if let Some(active) = active_session.as_ref().filter(|active| active.id == id) {
+            return Ok(active.to_session());
+        }
+    }
+
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    storage
+        .get_session(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session with ID {} not found", id))
+}
+
+/// Sets the details of the active or a stored session, returning the previous ones.
+pub fn update_details(
+    app_state: &crate::AppState,
+    id: &str,
+    details: SessionDetails,
+) -> Result<SessionDetails, String> {
+    {
+        let mut active_session = app_state.active_session.lock().map_err(|e| e.to_string())?;
+        if let Some(active) = active_session.as_mut().filter(|active| active.id == id) {
+            let previous = active.details();
+            active.set_details(details);
+            return Ok(previous);
         }
     }
+
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let mut session = storage
+        .get_session(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session with ID {} not found", id))?;
+
+    let previous = session.details();
+    session.set_details(details);
+
+    storage.save_session(&session).map_err(|e| e.to_string())?;
+    Ok(previous)
+}
+
+/// Asks the UI to collect details for a just-stopped session, if enabled in settings.
+pub fn request_details(app_handle: &AppHandle, session: &Session) {
+    let state = app_handle.state::<crate::AppState>();
+    let prompt = state
+        .settings
+        .lock()
+        .map(|settings| settings.prompt_for_details_on_stop)
+        .unwrap_or(false);
+
+    if !prompt {
+        return;
+    }
+
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+
+    app_handle
+        .emit("session-details-requested", session)
+        .unwrap_or_else(|e| log::error!("Failed to emit session-details-requested event: {:?}", e));
 }
 
 // Tauri command functions
+/// Starts a session, named and tagged by the template with `template_id` if given.
 #[tauri::command]
-pub async fn start_session(app_state: tauri::State<'_, crate::AppState>) -> Result<String, String> {
+pub async fn start_session(
+    template_id: Option<String>,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<String, String> {
     let mut session = app_state.active_session.lock().map_err(|e| e.to_string())?;
 
     if session.is_some() {
         return Err("A session is already in progress".to_string());
     }
 
-    let new_session = ActiveSession::new();
+    let new_session = match template_id {
+        Some(template_id) => {
+            let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+            let template = templates::find(&settings.templates, &template_id)
+                .ok_or_else(|| format!("Template with ID {} not found", template_id))?;
+            let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+            template.new_session(&storage)
+        }
+        None => ActiveSession::new(),
+    };
     let id = new_session.id.clone();
+
+    events::dispatch(
+        &handle,
+        AppEvent::SessionStarted {
+            session_id: id.clone(),
+            start_time: new_session.start_time,
+        },
+    );
+
     *session = Some(new_session);
 
     Ok(id)
 }
 
 #[tauri::command]
-pub async fn stop_session(app_state: tauri::State<'_, crate::AppState>) -> Result<Session, String> {
+pub async fn stop_session(
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
     let mut session_guard = app_state.active_session.lock().map_err(|e| e.to_string())?;
 
     match session_guard.take() {
         Some(active) => {
-            let completed = active.to_session();
+            let mut completed = active.to_session();
+
+            app_state
+                .scripts
+                .lock()
+                .map_err(|e| e.to_string())?
+                .process_session_stop(&mut completed);
 
-            // Save to storage
-            let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
-            storage
-                .save_session(&completed)
-                .map_err(|e| e.to_string())?;
+            // Save to storage, keeping the session active if that fails
+            let saved = app_state
+                .storage
+                .lock()
+                .map_err(|e| e.to_string())
+                .and_then(|mut storage| {
+                    storage.save_session(&completed).map_err(|e| e.to_string())
+                });
+            if let Err(err) = saved {
+                *session_guard = Some(active);
+                crate::encryption::request_unlock(&handle);
+                return Err(err);
+            }
+
+            events::dispatch(
+                &handle,
+                AppEvent::SessionStopped {
+                    session: completed.clone(),
+                },
+            );
+            request_details(&handle, &completed);
 
             Ok(completed)
         }
@@ -97,14 +505,40 @@ pub async fn stop_session(app_state: tauri::State<'_, crate::AppState>) -> Resul
 #[tauri::command]
 pub async fn add_marker(
     label: String,
+    category: Option<String>,
+    handle: tauri::AppHandle,
     app_state: tauri::State<'_, crate::AppState>,
 ) -> Result<Marker, String> {
     let mut session = app_state.active_session.lock().map_err(|e| e.to_string())?;
 
     match *session {
         Some(ref mut active) => {
-            let marker = active.add_marker(&label);
-            Ok(marker.clone())
+            let mut marker = active.new_marker(&label);
+            marker.category = category;
+            let marker = {
+                let scripts = app_state.scripts.lock().map_err(|e| e.to_string())?;
+                scripts.process_marker(active, marker)
+            }
+            .ok_or_else(|| "Marker was dropped by a script".to_string())?;
+            let marker = active.push_marker(marker).clone();
+
+            undo::record(
+                &app_state,
+                Change::AddMarker {
+                    session_id: active.id.clone(),
+                    marker: marker.clone(),
+                },
+            );
+
+            events::dispatch(
+                &handle,
+                AppEvent::MarkerAdded {
+                    session_id: active.id.clone(),
+                    marker: marker.clone(),
+                },
+            );
+
+            Ok(marker)
         }
         None => Err("No active session".to_string()),
     }
@@ -118,12 +552,143 @@ pub async fn get_active_session(
     Ok(session.clone())
 }
 
+/// Lists stored sessions, optionally only those carrying all of `tags`.
 #[tauri::command]
 pub async fn get_sessions(
+    tags: Option<Vec<String>>,
     app_state: tauri::State<'_, crate::AppState>,
 ) -> Result<Vec<Session>, String> {
     let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
-    storage.list_sessions().map_err(|e| e.to_string())
+    let mut sessions = storage.list_sessions().map_err(|e| e.to_string())?;
+
+    if let Some(tags) = tags {
+        sessions.retain(|session| tags.iter().all(|tag| session.has_tag(tag)));
+    }
+
+    Ok(sessions)
+}
+
+/// All tags in use, sorted alphabetically.
+#[tauri::command]
+pub async fn get_tags(app_state: tauri::State<'_, crate::AppState>) -> Result<Vec<String>, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let sessions = storage.list_sessions().map_err(|e| e.to_string())?;
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in sessions.iter().flat_map(|session| &session.tags) {
+        if !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.clone());
+        }
+    }
+    tags.sort_by_key(|tag| tag.to_lowercase());
+
+    Ok(tags)
+}
+
+#[tauri::command]
+pub async fn set_active_session_details(
+    details: SessionDetails,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<ActiveSession, String> {
+    let id = {
+        let session = app_state.active_session.lock().map_err(|e| e.to_string())?;
+        match *session {
+            Some(ref active) => active.id.clone(),
+            None => return Err("No active session".to_string()),
+        }
+    };
+
+    set_details(&app_state, &id, details)?;
+
+    let session = app_state.active_session.lock().map_err(|e| e.to_string())?;
+    session
+        .clone()
+        .ok_or_else(|| "No active session".to_string())
+}
+
+#[tauri::command]
+pub async fn set_session_details(
+    id: String,
+    details: SessionDetails,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    set_details(&app_state, &id, details)?;
+    find_session(&app_state, &id)
+}
+
+/// Sets the details of a session and records the change for undo.
+fn set_details(
+    app_state: &crate::AppState,
+    id: &str,
+    details: SessionDetails,
+) -> Result<(), String> {
+    let after = details.normalized();
+    let before = update_details(app_state, id, after.clone())?;
+
+    undo::record(
+        app_state,
+        Change::SetDetails {
+            session_id: id.to_string(),
+            before,
+            after,
+        },
+    );
+
+    Ok(())
+}
+
+/// Changes the label, notes and category of a marker in the active or a stored session.
+#[tauri::command]
+pub async fn edit_marker(
+    session_id: String,
+    marker_id: String,
+    label: String,
+    notes: Option<String>,
+    category: Option<String>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Marker, String> {
+    let (before, after) = update_markers(&app_state, &session_id, |markers| {
+        let marker = markers
+            .iter_mut()
+            .find(|marker| marker.id == marker_id)
+            .ok_or_else(|| format!("Marker with ID {} not found", marker_id))?;
+
+        let before = marker.clone();
+        marker.label = label;
+        marker.notes = notes;
+        marker.category = category;
+        Ok((before, marker.clone()))
+    })?;
+
+    undo::record(
+        &app_state,
+        Change::EditMarker {
+            session_id,
+            before,
+            after: after.clone(),
+        },
+    );
+
+    Ok(after)
+}
+
+#[tauri::command]
+pub async fn delete_marker(
+    session_id: String,
+    marker_id: String,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    let marker = update_markers(&app_state, &session_id, |markers| {
+        let index = markers
+            .iter()
+            .position(|marker| marker.id == marker_id)
+            .ok_or_else(|| format!("Marker with ID {} not found", marker_id))?;
+        Ok(markers.remove(index))
+    })?;
+
+    undo::record(&app_state, Change::DeleteMarker { session_id, marker });
+
+    Ok(())
 }
 
 #[tauri::command]
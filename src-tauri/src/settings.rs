@@ -0,0 +1,131 @@
+use crate::{
+    backups::BackupSettings, hooks::Hook, obs::ObsSettings,
+    recording_watcher::RecordingWatcherSettings, sync::SyncSettings, templates::SessionTemplate,
+    timecode::FrameRate, trash::TrashSettings, webhooks::WebhookTarget,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+/// User configurable settings, persisted as `settings.json` in the data directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub obs: ObsSettings,
+    pub hooks: Vec<Hook>,
+    pub webhooks: Vec<WebhookTarget>,
+    pub recording_watcher: RecordingWatcherSettings,
+    /// Frame rate for timecodes of sessions that don't set their own.
+    pub default_frame_rate: FrameRate,
+    /// Ask for a name, description and tags whenever a session stops.
+    pub prompt_for_details_on_stop: bool,
+    pub templates: Vec<SessionTemplate>,
+    pub trash: TrashSettings,
+    pub backups: BackupSettings,
+    pub sync: SyncSettings,
+}
+
+impl Settings {
+    fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join("settings.json")
+    }
+
+    /// Loads the settings from `data_dir`, falling back to defaults if none were saved yet.
+    /// Templates written without an ID get one, saved right away so it stays
+    /// the same across restarts.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let file_path = Self::path(data_dir);
+
+        if !file_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let file = File::open(&file_path)
+            .with_context(|| format!("Failed to open file at {:?}", file_path))?;
+
+        let reader = BufReader::new(file);
+        let mut settings: Self = serde_json::from_reader(reader)
+            .with_context(|| format!("Failed to deserialize settings from {:?}", file_path))?;
+
+        if crate::templates::assign_ids(&mut settings.templates) {
+            if let Err(err) = settings.save(data_dir) {
+                log::warn!("Failed to save new template IDs: {:?}", err);
+            }
+        }
+
+        Ok(settings)
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        crate::storage::write_json_atomic(&Self::path(data_dir), self)
+    }
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn get_settings(app_state: tauri::State<'_, crate::AppState>) -> Result<Settings, String> {
+    let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+pub async fn update_settings(
+    mut settings: Settings,
+    handle: tauri::AppHandle,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Settings, String> {
+    crate::templates::assign_ids(&mut settings.templates);
+
+    let turning_on_sync = settings.sync.enabled
+        && !app_state
+            .settings
+            .lock()
+            .map_err(|e| e.to_string())?
+            .sync
+            .enabled;
+    if turning_on_sync && crate::encryption::is_enabled() {
+        return Err("Sync can't be used while the session store is encrypted".to_string());
+    }
+
+    {
+        let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+        settings
+            .save(storage.data_dir())
+            .map_err(|e| e.to_string())?;
+    }
+
+    let previous = {
+        let mut current = app_state.settings.lock().map_err(|e| e.to_string())?;
+        std::mem::replace(&mut *current, settings.clone())
+    };
+
+    if previous.sync != settings.sync {
+        let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+        let target = crate::sync::target(storage.data_dir(), &settings.sync);
+        storage.set_sync(target);
+    }
+
+    if previous.obs != settings.obs {
+        let mut obs = app_state.obs.lock().map_err(|e| e.to_string())?;
+        obs.restart(&handle, &settings.obs);
+    }
+
+    if previous.recording_watcher != settings.recording_watcher {
+        let mut watcher = app_state
+            .recording_watcher
+            .lock()
+            .map_err(|e| e.to_string())?;
+        watcher.restart(&handle, &settings.recording_watcher);
+    }
+
+    if previous.templates != settings.templates {
+        crate::hotkey::register_template_shortcuts(&handle, &previous.templates, &settings.templates);
+        crate::tray::refresh_tray_menu(&handle);
+    }
+
+    Ok(settings)
+}
@@ -0,0 +1,268 @@
+//! Splitting a stored session in two and merging adjacent sessions into one.
+//!
+//! The sessions that were split or merged are moved to the archive folder of
+//! the project rather than deleted, and the new sessions list them in
+//! `derived_from`. Restoring an original brings it back and removes the
+//! sessions that were made from it.
+
+use crate::session::{Marker, MediaOffset, OffsetSource, Session};
+use crate::undo::{self, Change};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Where to split a session.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SplitPoint {
+    Timestamp { timestamp: DateTime<Utc> },
+    /// Just before the marker, which starts the second session.
+    Marker { marker_id: String },
+}
+
+impl SplitPoint {
+    /// Time of the split since the session started.
+    fn offset_in(&self, session: &Session) -> Result<Duration, String> {
+        match self {
+            SplitPoint::Timestamp { timestamp } => Ok(*timestamp - session.start_time),
+            SplitPoint::Marker { marker_id } => session
+                .markers
+                .iter()
+                .find(|marker| &marker.id == marker_id)
+                .map(|marker| session.session_time(marker))
+                .ok_or_else(|| format!("Marker with ID {} not found", marker_id)),
+        }
+    }
+}
+
+fn part_name(session: &Session, part: usize) -> Option<String> {
+    session
+        .name
+        .as_ref()
+        .map(|name| format!("{} (part {})", name, part))
+}
+
+/// Marker with its monotonic time shifted by `shift`, if it has one.
+fn shift_marker(marker: &Marker, shift: Duration) -> Marker {
+    let mut marker = marker.clone();
+    marker.elapsed_ms = marker
+        .elapsed_ms
+        .map(|elapsed_ms| (elapsed_ms as i64 + shift.num_milliseconds()).max(0) as u64);
+    marker
+}
+
+/// Splits `session` at `at`; markers before the split go to the first session
+/// and the rest to the second.
+pub fn split(session: &Session, at: &SplitPoint) -> Result<(Session, Session), String> {
+    let offset = at.offset_in(session)?;
+    if offset <= Duration::zero() || offset >= session.duration() {
+        return Err("The split point must be inside the session".to_string());
+    }
+
+    let (before, after): (Vec<&Marker>, Vec<&Marker>) = session
+        .markers
+        .iter()
+        .partition(|marker| session.session_time(marker) < offset);
+
+    let mut first = session.clone();
+    first.id = Uuid::new_v4().to_string();
+    first.end_time = session.start_time + offset;
+    first.elapsed_ms = Some(offset.num_milliseconds() as u64);
+    first.markers = before.into_iter().cloned().collect();
+    first.name = part_name(session, 1);
+    first.derived_from = vec![session.id.clone()];
+
+    let mut second = session.clone();
+    second.id = Uuid::new_v4().to_string();
+    second.start_time = session.start_time + offset;
+    second.elapsed_ms = Some((session.duration() - offset).num_milliseconds() as u64);
+    second.markers = after
+        .into_iter()
+        .map(|marker| shift_marker(marker, -offset))
+        .collect();
+    second.name = part_name(session, 2);
+    second.derived_from = vec![session.id.clone()];
+
+    // The recording is the same, it just starts earlier relative to the second
+    // part. Without an offset the recording started with the original session.
+    let offset_ms = session
+        .media_offset
+        .as_ref()
+        .map_or(0, |media_offset| media_offset.offset_ms)
+        - offset.num_milliseconds();
+    second.media_offset = Some(MediaOffset {
+        offset_ms,
+        source: OffsetSource::Manual { offset_ms },
+    });
+
+    Ok((first, second))
+}
+
+/// Merges `sessions` into one. With `collapse_gaps`, the time between them is
+/// left out of marker times and the duration; otherwise it is kept.
+pub fn merge(mut sessions: Vec<Session>, collapse_gaps: bool) -> Result<Session, String> {
+    if sessions.len() < 2 {
+        return Err("At least two sessions are needed to merge".to_string());
+    }
+
+    sessions.sort_by_key(|session| session.start_time);
+
+    for pair in sessions.windows(2) {
+        if pair[1].start_time < pair[0].end_time {
+            return Err(format!(
+                "Sessions {} and {} overlap",
+                pair[0].id, pair[1].id
+            ));
+        }
+    }
+
+    let first = &sessions[0];
+    let mut merged = first.clone();
+    merged.id = Uuid::new_v4().to_string();
+    merged.markers = Vec::new();
+    merged.derived_from = sessions.iter().map(|session| session.id.clone()).collect();
+
+    let mut base = Duration::zero();
+    for session in &sessions {
+        if !collapse_gaps {
+            base = session.start_time - first.start_time;
+        }
+
+        merged.markers.extend(session.markers.iter().map(|marker| {
+            let mut marker = marker.clone();
+            marker.elapsed_ms =
+                Some((base + session.session_time(&marker)).num_milliseconds().max(0) as u64);
+            marker
+        }));
+
+        for tag in &session.tags {
+            if !merged.has_tag(tag) {
+                merged.tags.push(tag.clone());
+            }
+        }
+
+        for media in &session.media {
+            if !merged.media.iter().any(|existing| existing.path == media.path) {
+                merged.media.push(media.clone());
+            }
+        }
+
+        if merged.recording_path.is_none() {
+            merged.recording_path = session.recording_path.clone();
+        }
+
+        base += session.duration();
+    }
+
+    let last = sessions.last().unwrap();
+    merged.end_time = last.end_time;
+    merged.elapsed_ms = Some(base.num_milliseconds() as u64);
+
+    Ok(merged)
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn split_session(
+    id: String,
+    at: SplitPoint,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<Session>, String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let session = storage
+        .get_session(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session with ID {} not found", id))?;
+
+    let (first, second) = split(&session, &at)?;
+
+    storage.save_session(&first).map_err(|e| e.to_string())?;
+    storage.save_session(&second).map_err(|e| e.to_string())?;
+    storage.archive_session(&id).map_err(|e| e.to_string())?;
+    drop(storage);
+
+    let parts = vec![first, second];
+    undo::record(
+        &app_state,
+        Change::Split {
+            original_id: id,
+            parts: parts.clone(),
+        },
+    );
+
+    Ok(parts)
+}
+
+#[tauri::command]
+pub async fn merge_sessions(
+    ids: Vec<String>,
+    collapse_gaps: Option<bool>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+
+    let mut sessions = Vec::new();
+    for id in &ids {
+        let session = storage
+            .get_session(id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Session with ID {} not found", id))?;
+        sessions.push(session);
+    }
+
+    let merged = merge(sessions, collapse_gaps.unwrap_or(false))?;
+
+    storage.save_session(&merged).map_err(|e| e.to_string())?;
+    for id in &ids {
+        storage.archive_session(id).map_err(|e| e.to_string())?;
+    }
+    drop(storage);
+
+    undo::record(
+        &app_state,
+        Change::Merge {
+            original_ids: ids,
+            merged: merged.clone(),
+        },
+    );
+
+    Ok(merged)
+}
+
+#[tauri::command]
+pub async fn get_archived_sessions(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<Session>, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    storage.list_archived_sessions().map_err(|e| e.to_string())
+}
+
+/// Brings an archived original back and moves the sessions split or merged
+/// from it to the trash, so edits made to them since can still be recovered.
+#[tauri::command]
+pub async fn restore_archived_session(
+    id: String,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+
+    let derived: Vec<String> = storage
+        .list_sessions()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|session| session.derived_from.contains(&id))
+        .map(|session| session.id)
+        .collect();
+
+    let session = storage
+        .restore_archived_session(&id)
+        .map_err(|e| e.to_string())?;
+
+    for derived_id in derived {
+        storage
+            .trash_session(&derived_id)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(session)
+}
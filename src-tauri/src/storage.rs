@@ -1,5 +1,11 @@
+use crate::encryption;
+use crate::migrations;
+use crate::projects;
 use crate::session::Session;
-use anyhow::{Context, Result};
+use crate::sync::SyncTarget;
+use crate::timecode::{self, FrameRate};
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
     fs::{self, File},
@@ -10,97 +16,614 @@ use std::{
 #[derive(Debug)]
 pub struct Storage {
     data_dir: PathBuf,
+    /// Where sessions of the active project are kept.
+    sessions_dir: PathBuf,
+    /// Shared folder that saved and removed sessions are synced through.
+    sync: Option<SyncTarget>,
 }
 
 impl Storage {
-    pub fn new() -> Self {
-        let app_data_dir = dirs::data_dir()
-            .expect("Could not determine app data directory")
-            .join("dibikaandaagozi");
+    /// Storage in `data_dir`, which must exist.
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            sessions_dir: data_dir.clone(),
+            data_dir,
+            sync: None,
+        }
+    }
 
-        // Create directory if it doesn't exist
-        fs::create_dir_all(&app_data_dir).expect("Failed to create app data directory");
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
 
-        Self {
-            data_dir: app_data_dir,
+    pub fn sessions_dir(&self) -> &Path {
+        &self.sessions_dir
+    }
+
+    pub fn set_sessions_dir(&mut self, sessions_dir: PathBuf) {
+        self.sessions_dir = sessions_dir;
+    }
+
+    pub fn sync_target(&self) -> Option<&SyncTarget> {
+        self.sync.as_ref()
+    }
+
+    pub fn set_sync(&mut self, sync: Option<SyncTarget>) {
+        self.sync = sync;
+    }
+
+    /// Shares a session saved in the project, if sync is enabled. A failure
+    /// doesn't fail the save; the session is shared with the next sync.
+    fn sync_saved(&self, session: &Session) {
+        self.sync_saved_in(&self.sessions_dir, session);
+    }
+
+    /// Shares a session saved in the project with `sessions_dir`.
+    fn sync_saved_in(&self, sessions_dir: &Path, session: &Session) {
+        if let Some(sync) = &self.sync {
+            if let Err(err) = sync.push(&self.data_dir, sessions_dir, session) {
+                log::error!("Failed to sync session {}: {:?}", session.id, err);
+            }
+        }
+    }
+
+    /// Shares that a session left the project, if sync is enabled.
+    fn sync_removed(&self, id: &str) {
+        if let Some(sync) = &self.sync {
+            if let Err(err) = sync.push_deletion(&self.data_dir, &self.sessions_dir, id) {
+                log::error!("Failed to sync deletion of session {}: {:?}", id, err);
+            }
         }
     }
 
     fn session_path(&self, id: &str) -> PathBuf {
-        self.data_dir.join(format!("session_{}.json", id))
+        session_path_in(&self.sessions_dir, id)
     }
 
     pub fn save_session(&mut self, session: &Session) -> Result<()> {
-        let file_path = self.session_path(&session.id);
-        let file = File::create(&file_path)
-            .with_context(|| format!("Failed to create file at {:?}", file_path))?;
+        save_session_in(&self.sessions_dir, session)?;
+        self.sync_saved(session);
+        Ok(())
+    }
 
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &session)
-            .with_context(|| format!("Failed to serialize session to {:?}", file_path))?;
+    /// Saves a session in the project with `sessions_dir`, which need not be
+    /// the active one, and shares it if sync is enabled.
+    pub fn save_session_to(&mut self, sessions_dir: &Path, session: &Session) -> Result<()> {
+        fs::create_dir_all(sessions_dir)
+            .with_context(|| format!("Failed to create directory at {:?}", sessions_dir))?;
+        save_session_in(sessions_dir, session)?;
+        self.sync_saved_in(sessions_dir, session);
+        Ok(())
+    }
 
+    pub fn delete_session(&mut self, id: &str) -> Result<()> {
+        remove_session_file(&self.session_path(id))?;
+        self.sync_removed(id);
         Ok(())
     }
 
     pub fn get_session(&self, id: &str) -> Result<Option<Session>> {
-        let file_path = self.session_path(id);
+        get_session_in(&self.sessions_dir, id)
+    }
 
-        if !file_path.exists() {
-            return Ok(None);
-        }
+    pub fn list_sessions(&self) -> Result<Vec<Session>> {
+        list_sessions_in(&self.sessions_dir)
+    }
 
-        let file = File::open(&file_path)
-            .with_context(|| format!("Failed to open file at {:?}", file_path))?;
+    fn archive_dir(&self) -> PathBuf {
+        archive_dir(&self.sessions_dir)
+    }
 
-        let reader = BufReader::new(file);
-        let session = serde_json::from_reader(reader)
-            .with_context(|| format!("Failed to deserialize session from {:?}", file_path))?;
+    /// Moves a session out of the project into its archive folder. Other
+    /// devices keep their copy, as archiving isn't deleting.
+    pub fn archive_session(&mut self, id: &str) -> Result<()> {
+        move_session(&self.sessions_dir, &self.archive_dir(), id)
+    }
 
-        Ok(Some(session))
+    pub fn list_archived_sessions(&self) -> Result<Vec<Session>> {
+        list_sessions_in(&self.archive_dir())
     }
 
-    pub fn list_sessions(&self) -> Result<Vec<Session>> {
-        let mut sessions: Vec<Session> = Vec::new();
-
-        for entry in fs::read_dir(&self.data_dir)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("json") {
-                if let Some(file_name) = path.file_name().and_then(|name| name.to_str()) {
-                    if file_name.starts_with("session_") {
-                        if let Ok(file) = File::open(&path) {
-                            let reader = BufReader::new(file);
-                            if let Ok(session) = serde_json::from_reader(reader) {
-                                sessions.push(session);
-                            }
-                        }
+    /// Moves an archived session back into the project.
+    pub fn restore_archived_session(&mut self, id: &str) -> Result<Session> {
+        move_session(&self.archive_dir(), &self.sessions_dir, id)?;
+
+        let session = self
+            .get_session(id)?
+            .with_context(|| format!("Restored session {} is missing", id))?;
+        self.sync_saved(&session);
+
+        Ok(session)
+    }
+
+    /// Moves a session into the trash of the project, stamped with when it was deleted.
+    pub fn trash_session(&mut self, id: &str) -> Result<()> {
+        trash_session_in(&self.sessions_dir, id)?;
+        self.sync_removed(id);
+        Ok(())
+    }
+
+    pub fn list_trashed_sessions(&self) -> Result<Vec<Session>> {
+        list_sessions_in(&trash_dir(&self.sessions_dir))
+    }
+
+    pub fn restore_trashed_session(&mut self, id: &str) -> Result<Session> {
+        move_session(&trash_dir(&self.sessions_dir), &self.sessions_dir, id)?;
+
+        let mut session = self
+            .get_session(id)?
+            .with_context(|| format!("Restored session {} is missing", id))?;
+        session.trashed_at = None;
+        self.save_session(&session)?;
+
+        Ok(session)
+    }
+
+    /// Permanently deletes a session from the trash.
+    pub fn purge_trashed_session(&mut self, id: &str) -> Result<()> {
+        remove_session_file(&session_path_in(&trash_dir(&self.sessions_dir), id))
+    }
+
+    /// Rewrites sessions of the project stored in an older schema version,
+    /// including archived and trashed ones. Returns how many were rewritten.
+    pub fn migrate_sessions(&mut self) -> Result<usize> {
+        let mut rewritten = 0;
+
+        for dir in [
+            self.sessions_dir.clone(),
+            self.archive_dir(),
+            trash_dir(&self.sessions_dir),
+        ] {
+            if !dir.exists() {
+                continue;
+            }
+
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if !is_session_file(&path) {
+                    continue;
+                }
+
+                match read_session_document(&path) {
+                    Ok((session, true)) => {
+                        save_session_in(&dir, &session)?;
+                        rewritten += 1;
                     }
+                    Ok((_, false)) => {}
+                    Err(err) => log::warn!("Not migrating unreadable session: {:?}", err),
                 }
             }
         }
 
-        // Sort by start time (newest first)
-        sessions.sort_by(|a, b| b.start_time.cmp(&a.start_time));
+        Ok(rewritten)
+    }
+
+    /// Tries to read every session file of every project, including archived
+    /// and trashed ones, and looks for temporary files left by interrupted
+    /// writes. With `repair`, unreadable files are replaced by their backup if
+    /// it can be read, and kept next to it with a `.corrupt` extension, and
+    /// leftover temporary files are deleted.
+    pub fn check_integrity(&mut self, repair: bool) -> Result<IntegrityReport> {
+        // Every encrypted session would look corrupt
+        if encryption::is_locked() {
+            bail!("Unlock the session store before checking it");
+        }
+
+        let mut report = IntegrityReport::default();
 
-        Ok(sessions)
+        let mut paths = Vec::new();
+        for dir in session_dirs(&self.data_dir)? {
+            for entry in fs::read_dir(&dir)? {
+                paths.push(entry?.path());
+            }
+        }
+
+        for path in paths {
+            if is_temp_file(&path) {
+                if repair {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("Failed to delete {:?}", path))?;
+                    log::info!("Deleted leftover temporary file {:?}", path);
+                }
+                report.temp_files.push(TempFile {
+                    removed: repair,
+                    path,
+                });
+                continue;
+            }
+
+            if !is_session_file(&path) {
+                continue;
+            }
+
+            report.checked += 1;
+            let Err(err) = read_session(&path) else {
+                continue;
+            };
+
+            log::warn!("Unreadable session file {:?}: {:?}", path, err);
+            let backup = backup_path(&path);
+            let recoverable = read_session(&backup).is_ok();
+
+            let recovered = if repair && recoverable {
+                fs::copy(&path, path.with_extension("json.corrupt"))
+                    .with_context(|| format!("Failed to keep corrupt file {:?}", path))?;
+                restore_backup(&path, &backup)?;
+                log::info!("Restored {:?} from its backup", path);
+                true
+            } else {
+                false
+            };
+
+            report.problems.push(IntegrityProblem {
+                path,
+                error: format!("{:#}", err),
+                recoverable,
+                recovered,
+            });
+        }
+
+        Ok(report)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntegrityReport {
+    /// Number of session files checked.
+    pub checked: usize,
+    pub problems: Vec<IntegrityProblem>,
+    /// Temporary files left by writes that were interrupted.
+    pub temp_files: Vec<TempFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TempFile {
+    pub path: PathBuf,
+    pub removed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityProblem {
+    pub path: PathBuf,
+    pub error: String,
+    /// Whether a readable backup exists.
+    pub recoverable: bool,
+    pub recovered: bool,
+}
+
+/// Path of the previous version of `path`, kept by `write_json_atomic`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".bak");
+    path.with_file_name(file_name)
+}
+
+/// Path `write_json_atomic` writes the new version of `path` to first.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Whether `path` is a temporary session file, left if writing it was interrupted.
+fn is_temp_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("session_") && name.ends_with(".json.tmp"))
+}
+
+/// Replaces the file at `path` with its backup through a temporary file, so a
+/// crash leaves either the damaged file or the restored one.
+fn restore_backup(path: &Path, backup: &Path) -> Result<()> {
+    let temp_path = temp_path(path);
+    fs::copy(backup, &temp_path)
+        .with_context(|| format!("Failed to copy {:?} to {:?}", backup, temp_path))?;
+    File::open(&temp_path)
+        .and_then(|file| file.sync_all())
+        .with_context(|| format!("Failed to sync {:?}", temp_path))?;
+    fs::rename(&temp_path, path)
+        .with_context(|| format!("Failed to move {:?} to {:?}", temp_path, path))?;
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// Makes a rename in the folder of `path` durable.
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Writes `value` as JSON to `path` through a temporary file that is synced
+/// and renamed over it, so a crash leaves either the old or the new version.
+/// The old version is kept as `<name>.bak`.
+pub fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let temp_path = temp_path(path);
+
+    let file = File::create(&temp_path)
+        .with_context(|| format!("Failed to create file at {:?}", temp_path))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, value)
+        .with_context(|| format!("Failed to serialize data to {:?}", temp_path))?;
+    let file = writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .with_context(|| format!("Failed to write {:?}", temp_path))?;
+    file.sync_all()
+        .with_context(|| format!("Failed to sync {:?}", temp_path))?;
+
+    // Only back up a readable previous version, so a backup is never corrupt
+    if is_valid_json(path) {
+        fs::copy(path, backup_path(path))
+            .with_context(|| format!("Failed to back up {:?}", path))?;
+    }
+
+    fs::rename(&temp_path, path)
+        .with_context(|| format!("Failed to move {:?} to {:?}", temp_path, path))?;
+
+    // Make the rename itself durable
+    sync_parent_dir(path);
+
+    Ok(())
+}
+
+fn is_valid_json(path: &Path) -> bool {
+    File::open(path).is_ok_and(|file| {
+        serde_json::from_reader::<_, serde_json::Value>(BufReader::new(file)).is_ok()
+    })
+}
+
+pub fn is_session_file(path: &Path) -> bool {
+    path.is_file()
+        && path.extension().and_then(|ext| ext.to_str()) == Some("json")
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("session_"))
+}
+
+/// Reads a session file, upgrading it from older schema versions in memory.
+pub fn read_session(path: &Path) -> Result<Session> {
+    read_session_document(path).map(|(session, _)| session)
+}
+
+/// Reads a session file that may be unencrypted although encryption is
+/// enabled, see `encryption::open_any`.
+pub fn read_any_session(path: &Path) -> Result<Session> {
+    session_from_any_document(read_document(path)?)
+        .map(|(session, _)| session)
+        .with_context(|| format!("Invalid session in {:?}", path))
+}
+
+/// Reads a session file, and whether it was stored in an older schema version.
+fn read_session_document(path: &Path) -> Result<(Session, bool)> {
+    session_from_document(read_document(path)?)
+        .with_context(|| format!("Invalid session in {:?}", path))
+}
+
+fn read_document(path: &Path) -> Result<serde_json::Value> {
+    let file = File::open(path).with_context(|| format!("Failed to open file at {:?}", path))?;
+
+    let reader = BufReader::new(file);
+    serde_json::from_reader(reader)
+        .with_context(|| format!("Failed to parse session from {:?}", path))
+}
+
+/// Deserializes a stored session document, upgrading it from older schema
+/// versions, and whether it had to be upgraded.
+pub fn session_from_document(document: serde_json::Value) -> Result<(Session, bool)> {
+    session_from_opened(encryption::open(document)?)
+}
+
+/// Like `session_from_document`, but also takes unencrypted documents while
+/// encryption is enabled, like sessions backed up before it was.
+pub fn session_from_any_document(document: serde_json::Value) -> Result<(Session, bool)> {
+    session_from_opened(encryption::open_any(document)?)
+}
+
+fn session_from_opened(mut document: serde_json::Value) -> Result<(Session, bool)> {
+    let migrated = migrations::migrate_session(&mut document).context("Failed to migrate session")?;
+
+    let session = serde_json::from_value(document).context("Failed to deserialize session")?;
+
+    Ok((session, migrated))
+}
+
+/// Session folders of every project, with their archive and trash folders,
+/// where they exist.
+pub fn session_dirs(data_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut project_dirs = vec![projects::sessions_dir(
+        data_dir,
+        projects::DEFAULT_PROJECT_ID,
+    )];
+    let projects_dir = data_dir.join("projects");
+    if projects_dir.is_dir() {
+        for entry in fs::read_dir(&projects_dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                project_dirs.push(path);
+            }
+        }
+    }
+
+    Ok(project_dirs
+        .into_iter()
+        .flat_map(|dir| [dir.clone(), archive_dir(&dir), trash_dir(&dir)])
+        .filter(|dir| dir.is_dir())
+        .collect())
+}
+
+/// Archive folder of the sessions in `sessions_dir`, holding sessions that
+/// were split or merged.
+pub fn archive_dir(sessions_dir: &Path) -> PathBuf {
+    sessions_dir.join("archive")
+}
+
+/// Trash folder of the sessions in `sessions_dir`.
+pub fn trash_dir(sessions_dir: &Path) -> PathBuf {
+    sessions_dir.join("trash")
+}
+
+/// Moves a session in `sessions_dir` into its trash, stamped with when it was deleted.
+pub fn trash_session_in(sessions_dir: &Path, id: &str) -> Result<()> {
+    let mut session = get_session_in(sessions_dir, id)?
+        .with_context(|| format!("Session with ID {} not found", id))?;
+    session.trashed_at = Some(Utc::now());
+
+    save_session_in(sessions_dir, &session)?;
+    move_session(sessions_dir, &trash_dir(sessions_dir), id)
+}
+
+/// Deletes a session file along with its backup.
+pub fn remove_session_file(path: &Path) -> Result<()> {
+    fs::remove_file(path).with_context(|| format!("Failed to delete session at {:?}", path))?;
+
+    let backup = backup_path(path);
+    if backup.exists() {
+        let _ = fs::remove_file(backup);
+    }
+
+    Ok(())
+}
+
+/// Moves a session file, and its backup if there is one, to another folder.
+fn move_session(from_dir: &Path, to_dir: &Path, id: &str) -> Result<()> {
+    fs::create_dir_all(to_dir)
+        .with_context(|| format!("Failed to create directory at {:?}", to_dir))?;
+
+    let from = session_path_in(from_dir, id);
+    let to = session_path_in(to_dir, id);
+    fs::rename(&from, &to).with_context(|| format!("Failed to move {:?} to {:?}", from, to))?;
+
+    if backup_path(&from).exists() {
+        let _ = fs::rename(backup_path(&from), backup_path(&to));
+    }
+
+    Ok(())
+}
+
+pub fn session_path_in(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("session_{}.json", id))
+}
+
+pub fn get_session_in(dir: &Path, id: &str) -> Result<Option<Session>> {
+    let file_path = session_path_in(dir, id);
+
+    if !file_path.exists() {
+        return Ok(None);
+    }
+
+    let session = read_session(&file_path)?;
+    // A file swapped in under another session's name reads fine otherwise
+    if session.id != id {
+        bail!("{:?} holds session {} instead of {}", file_path, session.id, id);
+    }
+    Ok(Some(session))
+}
+
+pub fn save_session_in(dir: &Path, session: &Session) -> Result<()> {
+    let document = serde_json::to_value(session)
+        .with_context(|| format!("Failed to serialize session {}", session.id))?;
+    let document = encryption::seal(migrations::stamp(document))?;
+    write_json_atomic(&session_path_in(dir, &session.id), &document)
+}
+
+/// Saves `session` over the file at `path`, dropping the backup of the
+/// previous version, which may have been stored under other keys.
+pub fn rewrite_session_file(path: &Path, session: &Session) -> Result<()> {
+    let dir = path
+        .parent()
+        .with_context(|| format!("{:?} has no parent directory", path))?;
+    save_session_in(dir, session)?;
+
+    let backup = backup_path(path);
+    if backup.exists() {
+        fs::remove_file(&backup).with_context(|| format!("Failed to delete {:?}", backup))?;
+    }
+
+    Ok(())
+}
+
+/// Reads every session stored in `dir`, newest first. Unreadable files are
+/// logged and skipped; `check_storage` reports and repairs them. Fails while
+/// the store is locked, rather than reporting no sessions.
+pub fn list_sessions_in(dir: &Path) -> Result<Vec<Session>> {
+    let mut sessions: Vec<Session> = Vec::new();
+
+    if !dir.exists() {
+        return Ok(sessions);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if is_session_file(&path) {
+            match read_session(&path) {
+                Ok(session) => sessions.push(session),
+                Err(err) if encryption::is_locked() => return Err(err),
+                Err(err) => log::warn!("Skipping unreadable session file: {:?}", err),
+            }
+        }
+    }
+
+    // Sort by start time (newest first)
+    sessions.sort_by(|a, b| b.start_time.cmp(&a.start_time));
+
+    Ok(sessions)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ExportFormat {
     JSON,
     CSV,
     Markdown,
 }
 
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::JSON => "json",
+            ExportFormat::CSV => "csv",
+            ExportFormat::Markdown => "md",
+        }
+    }
+}
+
+/// Writes `session` to `path` in `format`.
+pub fn export_to(
+    session: &Session,
+    path: &Path,
+    format: ExportFormat,
+    default_rate: FrameRate,
+) -> Result<(), io::Error> {
+    match format {
+        ExportFormat::JSON => export_json(session, path),
+        ExportFormat::CSV => export_csv(session, path, default_rate),
+        ExportFormat::Markdown => export_markdown(session, path, default_rate),
+    }
+}
+
 #[tauri::command]
 pub async fn export_session(
     id: String,
-    format: ExportFormat,
+    format: Option<ExportFormat>,
     handle: tauri::AppHandle,
     app_state: tauri::State<'_, crate::AppState>,
 ) -> Result<String, String> {
+    let settings = app_state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone();
+    let default_rate = settings.default_frame_rate;
+
     let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
 
     // Get the session
@@ -110,6 +633,14 @@ pub async fn export_session(
         Err(e) => return Err(e.to_string()),
     };
 
+    // Fall back to the format of the session's template, then JSON
+    let format = format
+        .or_else(|| {
+            let template_id = session.template_id.as_deref()?;
+            crate::templates::find(&settings.templates, template_id)?.export_format
+        })
+        .unwrap_or(ExportFormat::JSON);
+
     // Use the plugin dialog
     use tauri_plugin_dialog::DialogExt;
 
@@ -120,11 +651,7 @@ pub async fn export_session(
         ExportFormat::Markdown => "Markdown",
     };
 
-    let filter_ext = match format {
-        ExportFormat::JSON => "json",
-        ExportFormat::CSV => "csv",
-        ExportFormat::Markdown => "md",
-    };
+    let filter_ext = format.extension();
 
     // Prompt user for save location
     let file_path = handle
@@ -138,16 +665,54 @@ pub async fn export_session(
     let save_path = file_path.as_path().expect("Failed to get save file path");
 
     // Export to the selected format
-    match format {
-        ExportFormat::JSON => export_json(&session, &save_path),
-        ExportFormat::CSV => export_csv(&session, &save_path),
-        ExportFormat::Markdown => export_markdown(&session, &save_path),
-    }
-    .map_err(|e| e.to_string())?;
+    export_to(&session, save_path, format, default_rate).map_err(|e| e.to_string())?;
+
+    crate::events::dispatch(
+        &handle,
+        crate::events::AppEvent::ExportFinished {
+            session_id: session.id.clone(),
+            format,
+            path: save_path.to_path_buf(),
+        },
+    );
 
     Ok("Session exported successfully".to_string())
 }
 
+/// Checks the session files of every project, restoring unreadable ones from
+/// their backups and deleting leftover temporary files if `repair` is set.
+#[tauri::command]
+pub async fn check_storage(
+    repair: Option<bool>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<IntegrityReport, String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    storage
+        .check_integrity(repair.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+/// Upgrades session files of the active project stored in an older format.
+#[tauri::command]
+pub async fn migrate_sessions(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<usize, String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    storage.migrate_sessions().map_err(|e| e.to_string())
+}
+
+/// Formats a media time as `HH:MM:SS.mmm`, prefixed with `-` for markers
+/// placed before the recording started.
+pub fn format_media_time(time: Duration) -> String {
+    let sign = if time < Duration::zero() { "-" } else { "" };
+    format!("{}{}", sign, crate::obs::format_timecode(time.num_milliseconds().abs()))
+}
+
+/// Quotes `value` as a CSV field, doubling any quotes in it.
+pub fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
 fn export_json(session: &Session, path: &Path) -> Result<(), io::Error> {
     let file = File::create(path)?;
     let writer = BufWriter::new(file);
@@ -155,32 +720,48 @@ fn export_json(session: &Session, path: &Path) -> Result<(), io::Error> {
     Ok(())
 }
 
-fn export_csv(session: &Session, path: &Path) -> Result<(), io::Error> {
+fn export_csv(session: &Session, path: &Path, default_rate: FrameRate) -> Result<(), io::Error> {
     let mut file = File::create(path)?;
 
     // Write header
-    writeln!(file, "Marker ID,Timestamp,Label,Notes")?;
+    writeln!(file, "Marker ID,Timestamp,Media Time,Timecode,Category,Label,Notes")?;
 
     // Write markers
     for marker in &session.markers {
         writeln!(
             file,
-            "{},{},\"{}\",\"{}\"",
+            "{},{},{},{},{},{},{}",
             marker.id,
             marker.timestamp.to_rfc3339(),
-            marker.label.replace("\"", "\"\""),
-            marker.notes.as_deref().unwrap_or("").replace("\"", "\"\"")
+            format_media_time(session.media_time(marker)),
+            timecode::marker_timecode(session, marker, default_rate),
+            csv_field(marker.category.as_deref().unwrap_or("")),
+            csv_field(&marker.label),
+            csv_field(marker.notes.as_deref().unwrap_or(""))
         )?;
     }
 
     Ok(())
 }
 
-fn export_markdown(session: &Session, path: &Path) -> Result<(), io::Error> {
+fn export_markdown(
+    session: &Session,
+    path: &Path,
+    default_rate: FrameRate,
+) -> Result<(), io::Error> {
     let mut file = File::create(path)?;
 
     // Write header
-    writeln!(file, "# Session Report: {}", session.id)?;
+    writeln!(
+        file,
+        "# Session Report: {}",
+        session.name.as_deref().unwrap_or(&session.id)
+    )?;
+
+    if let Some(description) = &session.description {
+        writeln!(file, "\n{}\n", description)?;
+    }
+
     writeln!(
         file,
         "- **Start Time**: {}",
@@ -192,28 +773,67 @@ fn export_markdown(session: &Session, path: &Path) -> Result<(), io::Error> {
         session.end_time.format("%Y-%m-%d %H:%M:%S")
     )?;
 
-    let duration = session.end_time - session.start_time;
+    let duration = session.duration();
     let hours = duration.num_hours();
     let minutes = duration.num_minutes() % 60;
     let seconds = duration.num_seconds() % 60;
 
     writeln!(file, "- **Duration**: {}h {}m {}s", hours, minutes, seconds)?;
 
+    if !session.tags.is_empty() {
+        writeln!(file, "- **Tags**: {}", session.tags.join(", "))?;
+    }
+
+    if let Some(recording_path) = &session.recording_path {
+        writeln!(file, "- **Recording**: {}", recording_path)?;
+    }
+
+    writeln!(
+        file,
+        "- **Frame Rate**: {}",
+        timecode::session_rate(session, default_rate)
+    )?;
+
+    if let Some(offset) = &session.media_offset {
+        writeln!(
+            file,
+            "- **Media Offset**: {:+.3}s",
+            offset.offset_ms as f64 / 1000.0
+        )?;
+    }
+
+    if !session.media.is_empty() {
+        writeln!(file, "\n## Media\n")?;
+        for media in &session.media {
+            writeln!(
+                file,
+                "- {} ({:.1} MB)",
+                media.path.display(),
+                media.size as f64 / 1_000_000.0
+            )?;
+        }
+    }
+
     writeln!(file, "\n## Markers\n")?;
-    writeln!(file, "| Time | Timestamp | Label | Notes |")?;
-    writeln!(file, "|------|-----------|-------|-------|")?;
+    writeln!(file, "| Time | Timecode | Timestamp | Category | Label | Notes |")?;
+    writeln!(file, "|------|----------|-----------|----------|-------|-------|")?;
 
     for marker in &session.markers {
-        let relative_time = marker.timestamp - session.start_time;
+        let relative_time = session.media_time(marker);
+        let sign = if relative_time < Duration::zero() { "-" } else { "" };
+        let relative_time = relative_time.abs();
         let rel_minutes = relative_time.num_minutes();
         let rel_seconds = relative_time.num_seconds() % 60;
 
         writeln!(
             file,
-            "| {}m {}s | {} | {} | {} |",
+            "| {}{}m {}s | {} | {} | {} | {} | {} |",
+            sign,
             rel_minutes,
             rel_seconds,
+            timecode::marker_timecode(session, marker, default_rate),
             marker.timestamp.format("%H:%M:%S"),
+            marker.category.as_deref().unwrap_or("-"),
             marker.label,
             marker.notes.as_deref().unwrap_or("-")
         )?;
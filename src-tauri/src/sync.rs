@@ -0,0 +1,909 @@
+//! Sharing sessions between devices through a folder synced by another tool,
+//! like Syncthing or Nextcloud.
+//!
+//! Each device only ever writes below its own folder, `devices/<device id>/`
+//! in the shared folder, so the sync tool never sees two devices edit the
+//! same file. There it keeps a snapshot of every session it saved, laid out
+//! like the data directory, and appends each change to `log.jsonl`, a record
+//! for troubleshooting that is rotated to `log.1.jsonl` once it grows past
+//! 1 MiB. Snapshots carry a version vector: how many changes of each device
+//! they include.
+//!
+//! Pulling compares the snapshots of other devices with the version last
+//! synced here, kept in `sync/` in the data directory. Newer snapshots replace
+//! the local session; snapshots edited concurrently are merged marker by
+//! marker. Markers, details or other properties changed differently on both
+//! sides keep the local version and are listed as conflicts until resolved.
+//! Only the active project is synced. Sessions archived after a split or merge
+//! stay as they are; the sessions made from them are synced instead.
+
+use crate::{
+    session::{Marker, Session, SessionDetails},
+    storage::{self, Storage},
+    AppState,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+    time::Duration as StdDuration,
+};
+use tauri::{AppHandle, Emitter as _, Manager};
+use uuid::Uuid;
+
+const SYNC_INTERVAL: StdDuration = StdDuration::from_secs(60);
+const DEVICES_DIR: &str = "devices";
+const LOG_NAME: &str = "log.jsonl";
+const ROTATED_LOG_NAME: &str = "log.1.jsonl";
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncSettings {
+    pub enabled: bool,
+    /// The shared folder, kept in sync between devices by another tool.
+    pub folder: Option<PathBuf>,
+}
+
+/// Changes of each device included in a version, by device ID.
+type VersionVector = BTreeMap<String, u64>;
+
+/// Whether `a` includes every change in `b`.
+fn includes(a: &VersionVector, b: &VersionVector) -> bool {
+    b.iter()
+        .all(|(device, count)| a.get(device).is_some_and(|own| own >= count))
+}
+
+fn join(a: &VersionVector, b: &VersionVector) -> VersionVector {
+    let mut joined = a.clone();
+    for (device, count) in b {
+        let own = joined.entry(device.clone()).or_insert(0);
+        *own = (*own).max(*count);
+    }
+    joined
+}
+
+/// A device's latest version of a session, or of its deletion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    session_id: String,
+    device_id: String,
+    saved: DateTime<Utc>,
+    version: VersionVector,
+    /// `None` once the session was deleted.
+    session: Option<Session>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LogAction {
+    Saved,
+    Deleted,
+    Merged,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LogEntry {
+    at: DateTime<Utc>,
+    /// Sessions directory of the project, relative to the data directory.
+    project: PathBuf,
+    session_id: String,
+    action: LogAction,
+    version: VersionVector,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub id: String,
+    pub session_id: String,
+    /// Device whose version lost and was set aside.
+    pub discarded_device: String,
+    pub detected: DateTime<Utc>,
+    pub change: ConflictingChange,
+}
+
+/// What was changed differently on two devices. Every device merging the two
+/// keeps the same version, the one saved last, so they agree without asking;
+/// the other is kept here to be taken instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConflictingChange {
+    /// A marker edited on both, or edited on one and deleted on the other.
+    Marker {
+        kept: Option<Marker>,
+        discarded: Option<Marker>,
+    },
+    Details {
+        kept: SessionDetails,
+        discarded: SessionDetails,
+    },
+    /// Anything else about the session, like its media or timecode.
+    Properties { discarded: Box<Session> },
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    /// Sessions replaced by a newer version from another device.
+    pub updated: usize,
+    pub deleted: usize,
+    /// Sessions merged with a concurrent edit from another device.
+    pub merged: usize,
+    pub conflicts: usize,
+    /// Local sessions shared for the first time.
+    pub pushed: usize,
+}
+
+impl SyncReport {
+    fn changed_sessions(&self) -> bool {
+        self.updated + self.deleted + self.merged > 0
+    }
+}
+
+/// A shared folder sessions are synced through, as this device.
+#[derive(Debug, Clone)]
+pub struct SyncTarget {
+    folder: PathBuf,
+    device_id: String,
+}
+
+/// Where the sync state of one project is kept.
+struct SyncPaths {
+    /// The project's sessions directory, relative to the data directory.
+    project: PathBuf,
+    /// This device's snapshots of the project in the shared folder.
+    own: PathBuf,
+    /// Last synced versions and conflicts of the project.
+    state: PathBuf,
+}
+
+impl SyncPaths {
+    fn base_path(&self, id: &str) -> PathBuf {
+        storage::session_path_in(&self.state.join("base"), id)
+    }
+
+    fn conflicts_path(&self) -> PathBuf {
+        self.state.join("conflicts.json")
+    }
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = File::open(path).with_context(|| format!("Failed to open file at {:?}", path))?;
+    let value = serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("Failed to deserialize {:?}", path))?;
+
+    Ok(Some(value))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory at {:?}", parent))?;
+    }
+    storage::write_json_atomic(path, value)
+}
+
+/// This device's ID, made up the first time sync is used.
+fn device_id(data_dir: &Path) -> Result<String> {
+    let path = data_dir.join("sync").join("device-id");
+
+    if path.exists() {
+        let id = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read device ID from {:?}", path))?;
+        return Ok(id.trim().to_string());
+    }
+
+    let id = Uuid::new_v4().to_string();
+    fs::create_dir_all(data_dir.join("sync"))?;
+    fs::write(&path, &id).with_context(|| format!("Failed to write device ID to {:?}", path))?;
+    Ok(id)
+}
+
+/// The sync target for `settings`, if sync is enabled and set up.
+pub fn target(data_dir: &Path, settings: &SyncSettings) -> Option<SyncTarget> {
+    let folder = settings.folder.clone().filter(|_| settings.enabled)?;
+
+    match device_id(data_dir) {
+        Ok(device_id) => Some(SyncTarget { folder, device_id }),
+        Err(err) => {
+            log::error!("Failed to set up sync: {:?}", err);
+            None
+        }
+    }
+}
+
+impl SyncTarget {
+    fn devices_dir(&self) -> PathBuf {
+        self.folder.join(DEVICES_DIR)
+    }
+
+    fn paths(&self, data_dir: &Path, sessions_dir: &Path) -> SyncPaths {
+        let project = sessions_dir
+            .strip_prefix(data_dir)
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+
+        SyncPaths {
+            own: self.devices_dir().join(&self.device_id).join(&project),
+            state: data_dir.join("sync").join(&project),
+            project,
+        }
+    }
+
+    fn append_log(&self, paths: &SyncPaths, snapshot: &Snapshot, action: LogAction) -> Result<()> {
+        let device_dir = self.devices_dir().join(&self.device_id);
+        let path = device_dir.join(LOG_NAME);
+
+        // Keep only the latest changes, replacing the previous rotated log
+        if fs::metadata(&path).is_ok_and(|metadata| metadata.len() >= MAX_LOG_BYTES) {
+            let rotated = device_dir.join(ROTATED_LOG_NAME);
+            fs::rename(&path, &rotated)
+                .with_context(|| format!("Failed to move {:?} to {:?}", path, rotated))?;
+        }
+
+        let entry = LogEntry {
+            at: snapshot.saved,
+            project: paths.project.clone(),
+            session_id: snapshot.session_id.clone(),
+            action,
+            version: snapshot.version.clone(),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open change log at {:?}", path))?;
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("Failed to append to change log at {:?}", path))
+    }
+
+    /// Shares a new version of a session, or its deletion, built on `since`.
+    fn publish(
+        &self,
+        paths: &SyncPaths,
+        session_id: &str,
+        session: Option<&Session>,
+        since: VersionVector,
+        action: LogAction,
+    ) -> Result<Snapshot> {
+        let mut version = since;
+        *version.entry(self.device_id.clone()).or_insert(0) += 1;
+
+        let snapshot = Snapshot {
+            session_id: session_id.to_string(),
+            device_id: self.device_id.clone(),
+            saved: Utc::now(),
+            version,
+            session: session.cloned(),
+        };
+
+        write_json(&storage::session_path_in(&paths.own, session_id), &snapshot)?;
+        self.append_log(paths, &snapshot, action)?;
+        Ok(snapshot)
+    }
+
+    /// The last version of a session this device agreed on with the others,
+    /// which concurrent edits are merged against.
+    fn base(&self, paths: &SyncPaths, id: &str) -> Result<Option<Snapshot>> {
+        read_json(&paths.base_path(id))
+    }
+
+    /// Every change to a session this device has seen: those in the base and
+    /// its own since.
+    fn known_version(
+        &self,
+        paths: &SyncPaths,
+        id: &str,
+        base: Option<&Snapshot>,
+    ) -> Result<VersionVector> {
+        let base_version = base.map(|base| base.version.clone()).unwrap_or_default();
+        let own: Option<Snapshot> = read_json(&storage::session_path_in(&paths.own, id))?;
+
+        Ok(match own {
+            Some(own) => join(&base_version, &own.version),
+            None => base_version,
+        })
+    }
+
+    fn push_change(
+        &self,
+        paths: &SyncPaths,
+        id: &str,
+        session: Option<&Session>,
+        action: LogAction,
+    ) -> Result<()> {
+        let base = self.base(paths, id)?;
+        let since = self.known_version(paths, id, base.as_ref())?;
+
+        let snapshot = self.publish(paths, id, session, since, action)?;
+
+        // A session new to sync starts out agreed on
+        if base.is_none() {
+            write_json(&paths.base_path(id), &snapshot)?;
+        }
+
+        Ok(())
+    }
+
+    /// Shares a session that was just saved in `sessions_dir`.
+    pub fn push(&self, data_dir: &Path, sessions_dir: &Path, session: &Session) -> Result<()> {
+        let paths = self.paths(data_dir, sessions_dir);
+        self.push_change(&paths, &session.id, Some(session), LogAction::Saved)
+    }
+
+    /// Shares that a session was removed from `sessions_dir`.
+    pub fn push_deletion(&self, data_dir: &Path, sessions_dir: &Path, id: &str) -> Result<()> {
+        let paths = self.paths(data_dir, sessions_dir);
+        self.push_change(&paths, id, None, LogAction::Deleted)
+    }
+
+    /// Snapshots of the project by other devices.
+    fn remote_snapshots(&self, paths: &SyncPaths) -> Result<Vec<Snapshot>> {
+        let mut snapshots = Vec::new();
+        let devices_dir = self.devices_dir();
+
+        if !devices_dir.exists() {
+            return Ok(snapshots);
+        }
+
+        for device in fs::read_dir(&devices_dir)? {
+            let device = device?;
+            if device.file_name().to_str() == Some(self.device_id.as_str()) {
+                continue;
+            }
+
+            let dir = device.path().join(&paths.project);
+            if !dir.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if !storage::is_session_file(&path) {
+                    continue;
+                }
+                // A file the sync tool is still writing is picked up next time
+                match read_json::<Snapshot>(&path) {
+                    Ok(Some(snapshot)) => snapshots.push(snapshot),
+                    Ok(None) => {}
+                    Err(err) => log::warn!("Skipping unreadable snapshot: {:?}", err),
+                }
+            }
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Brings in the changes of other devices to the sessions in
+    /// `sessions_dir`, and shares local sessions that were never shared.
+    pub fn pull(&self, data_dir: &Path, sessions_dir: &Path) -> Result<SyncReport> {
+        let paths = self.paths(data_dir, sessions_dir);
+        let mut report = SyncReport::default();
+        let mut conflicts = Vec::new();
+
+        let archive_dir = storage::archive_dir(sessions_dir);
+        for remote in self.remote_snapshots(&paths)? {
+            // Archived here after a split or merge; the parts carry on instead
+            if storage::session_path_in(&archive_dir, &remote.session_id).exists() {
+                continue;
+            }
+
+            let base = self.base(&paths, &remote.session_id)?;
+            let known = self.known_version(&paths, &remote.session_id, base.as_ref())?;
+
+            if includes(&known, &remote.version) {
+                continue;
+            }
+
+            let local = storage::get_session_in(sessions_dir, &remote.session_id)?;
+            let base_session = base.and_then(|base| base.session);
+            let unchanged_here = local == base_session;
+
+            if unchanged_here && includes(&remote.version, &known) {
+                // Nothing changed here since the last sync; take theirs
+                match &remote.session {
+                    Some(session) => {
+                        storage::save_session_in(sessions_dir, session)?;
+                        report.updated += 1;
+                    }
+                    None if local.is_some() => {
+                        storage::trash_session_in(sessions_dir, &remote.session_id)?;
+                        report.deleted += 1;
+                    }
+                    None => {}
+                }
+                write_json(&paths.base_path(&remote.session_id), &remote)?;
+                continue;
+            }
+
+            let version = join(&known, &remote.version);
+            let merged = match (&local, &remote.session) {
+                (None, None) => None,
+                // Edited there while deleted here, or the other way around; keep the edits
+                (None, Some(remote_session)) => Some(remote_session.clone()),
+                (Some(local), None) => Some(local.clone()),
+                (Some(local), Some(remote_session)) => {
+                    // The version saved last wins conflicts, the same on every device
+                    let own: Option<Snapshot> =
+                        read_json(&storage::session_path_in(&paths.own, &remote.session_id))?;
+                    let remote_wins = own.is_none_or(|own| {
+                        (remote.saved, &remote.device_id) > (own.saved, &own.device_id)
+                    });
+                    let (winner, loser, loser_device) = if remote_wins {
+                        (remote_session, local, self.device_id.as_str())
+                    } else {
+                        (local, remote_session, remote.device_id.as_str())
+                    };
+                    Some(merge(
+                        base_session.as_ref(),
+                        winner,
+                        loser,
+                        loser_device,
+                        &mut conflicts,
+                    ))
+                }
+            };
+
+            if merged != local {
+                match &merged {
+                    Some(session) => storage::save_session_in(sessions_dir, session)?,
+                    None => storage::trash_session_in(sessions_dir, &remote.session_id)?,
+                }
+            }
+
+            if merged == remote.session {
+                // They already have this version; just note that it includes ours
+                let base = Snapshot {
+                    version,
+                    session: merged,
+                    ..remote
+                };
+                write_json(&paths.base_path(&base.session_id), &base)?;
+            } else {
+                let snapshot = self.publish(
+                    &paths,
+                    &remote.session_id,
+                    merged.as_ref(),
+                    version,
+                    LogAction::Merged,
+                )?;
+                write_json(&paths.base_path(&remote.session_id), &snapshot)?;
+            }
+            report.merged += 1;
+        }
+
+        if !conflicts.is_empty() {
+            report.conflicts = conflicts.len();
+            let mut all = load_conflicts(&paths)?;
+            all.extend(conflicts);
+            write_json(&paths.conflicts_path(), &all)?;
+        }
+
+        for session in storage::list_sessions_in(sessions_dir)? {
+            if !storage::session_path_in(&paths.own, &session.id).exists()
+                && !paths.base_path(&session.id).exists()
+            {
+                self.push(data_dir, sessions_dir, &session)?;
+                report.pushed += 1;
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// The one of `local` and `remote` that changed since `base`, if only one did.
+fn merge_value<T: PartialEq + Clone>(base: Option<&T>, local: &T, remote: &T) -> Option<T> {
+    if local == remote || base == Some(remote) {
+        Some(local.clone())
+    } else if base == Some(local) {
+        Some(remote.clone())
+    } else {
+        None
+    }
+}
+
+/// Everything about a session but its markers and details.
+fn properties(session: &Session) -> Session {
+    let mut properties = session.clone();
+    properties.markers = Vec::new();
+    properties.set_details(SessionDetails::default());
+    properties
+}
+
+fn find_marker<'a>(session: Option<&'a Session>, id: &str) -> Option<&'a Marker> {
+    session?.markers.iter().find(|marker| marker.id == id)
+}
+
+/// Merges concurrent edits of a session, marker by marker. Whatever changed
+/// differently on both sides takes the `winner`'s version, and the `loser`'s
+/// is recorded in `conflicts`.
+fn merge(
+    base: Option<&Session>,
+    winner: &Session,
+    loser: &Session,
+    loser_device: &str,
+    conflicts: &mut Vec<SyncConflict>,
+) -> Session {
+    let mut conflict = |change: ConflictingChange| {
+        conflicts.push(SyncConflict {
+            id: Uuid::new_v4().to_string(),
+            session_id: winner.id.clone(),
+            discarded_device: loser_device.to_string(),
+            detected: Utc::now(),
+            change,
+        })
+    };
+
+    let mut merged = match merge_value(
+        base.map(properties).as_ref(),
+        &properties(winner),
+        &properties(loser),
+    ) {
+        Some(properties) => properties,
+        None => {
+            conflict(ConflictingChange::Properties {
+                discarded: Box::new(loser.clone()),
+            });
+            properties(winner)
+        }
+    };
+
+    let winner_details = winner.details();
+    let loser_details = loser.details();
+    let details = match merge_value(
+        base.map(Session::details).as_ref(),
+        &winner_details,
+        &loser_details,
+    ) {
+        Some(details) => details,
+        None => {
+            conflict(ConflictingChange::Details {
+                kept: winner_details.clone(),
+                discarded: loser_details,
+            });
+            winner_details
+        }
+    };
+    merged.set_details(details);
+
+    let mut ids: Vec<&str> = Vec::new();
+    for marker in base
+        .into_iter()
+        .chain([winner, loser])
+        .flat_map(|session| &session.markers)
+    {
+        if !ids.contains(&marker.id.as_str()) {
+            ids.push(&marker.id);
+        }
+    }
+
+    for id in ids {
+        let winner_marker = find_marker(Some(winner), id).cloned();
+        let loser_marker = find_marker(Some(loser), id).cloned();
+        // Without a base, a marker missing on one side may be new or deleted
+        let base_marker = base.map(|base| find_marker(Some(base), id).cloned());
+        let marker = match merge_value(base_marker.as_ref(), &winner_marker, &loser_marker) {
+            Some(marker) => marker,
+            None => {
+                conflict(ConflictingChange::Marker {
+                    kept: winner_marker.clone(),
+                    discarded: loser_marker,
+                });
+                winner_marker
+            }
+        };
+
+        if let Some(marker) = marker {
+            crate::session::insert_marker(&mut merged.markers, marker);
+        }
+    }
+
+    merged
+}
+
+fn load_conflicts(paths: &SyncPaths) -> Result<Vec<SyncConflict>> {
+    Ok(read_json(&paths.conflicts_path())?.unwrap_or_default())
+}
+
+/// Syncs the active project now.
+pub fn sync_now(app_handle: &AppHandle) -> Result<SyncReport> {
+    let state = app_handle.state::<AppState>();
+
+    let report = {
+        let storage = state.storage.lock().unwrap();
+        let Some(target) = storage.sync_target() else {
+            anyhow::bail!("Sync is not enabled");
+        };
+        target.pull(storage.data_dir(), storage.sessions_dir())?
+    };
+
+    if report.changed_sessions() || report.conflicts > 0 {
+        app_handle
+            .emit("sessions-synced", &report)
+            .unwrap_or_else(|e| log::error!("Failed to emit sessions-synced event: {:?}", e));
+    }
+
+    Ok(report)
+}
+
+/// Syncs every minute while sync is enabled.
+pub fn spawn_syncer(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SYNC_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let enabled = {
+                let state = app_handle.state::<AppState>();
+                let storage = state.storage.lock().unwrap();
+                storage.sync_target().is_some() && !crate::encryption::is_locked()
+            };
+            if !enabled {
+                continue;
+            }
+
+            if let Err(err) = sync_now(&app_handle) {
+                log::error!("Failed to sync sessions: {:?}", err);
+            }
+        }
+    });
+}
+
+/// Paths of the active project, if sync is enabled.
+fn active_paths(storage: &Storage) -> Result<SyncPaths, String> {
+    let target = storage
+        .sync_target()
+        .ok_or_else(|| "Sync is not enabled".to_string())?;
+    Ok(target.paths(storage.data_dir(), storage.sessions_dir()))
+}
+
+/// Takes the discarded side of a conflict into `session`.
+fn apply_discarded(session: &mut Session, change: ConflictingChange) {
+    match change {
+        ConflictingChange::Marker { kept, discarded } => {
+            let id = discarded
+                .as_ref()
+                .or(kept.as_ref())
+                .map(|marker| marker.id.clone());
+            session
+                .markers
+                .retain(|marker| Some(&marker.id) != id.as_ref());
+            if let Some(marker) = discarded {
+                crate::session::insert_marker(&mut session.markers, marker);
+            }
+        }
+        ConflictingChange::Details { discarded, .. } => session.set_details(discarded),
+        ConflictingChange::Properties { discarded } => {
+            let mut updated = properties(&discarded);
+            updated.markers = std::mem::take(&mut session.markers);
+            updated.set_details(session.details());
+            *session = updated;
+        }
+    }
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn sync_sessions(handle: tauri::AppHandle) -> Result<SyncReport, String> {
+    sync_now(&handle).map_err(|e| format!("{:#}", e))
+}
+
+/// Unresolved conflicts of the active project.
+#[tauri::command]
+pub async fn get_sync_conflicts(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<SyncConflict>, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let paths = active_paths(&storage)?;
+    load_conflicts(&paths).map_err(|e| e.to_string())
+}
+
+/// Resolves a conflict by keeping the version that won, which is already in
+/// place, or by taking the discarded one instead.
+#[tauri::command]
+pub async fn resolve_sync_conflict(
+    id: String,
+    take_discarded: bool,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let paths = active_paths(&storage)?;
+
+    let mut conflicts = load_conflicts(&paths).map_err(|e| e.to_string())?;
+    let index = conflicts
+        .iter()
+        .position(|conflict| conflict.id == id)
+        .ok_or_else(|| format!("Conflict with ID {} not found", id))?;
+    let conflict = conflicts.remove(index);
+
+    if take_discarded {
+        let mut session = storage
+            .get_session(&conflict.session_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Session with ID {} not found", conflict.session_id))?;
+        apply_discarded(&mut session, conflict.change);
+        storage.save_session(&session).map_err(|e| e.to_string())?;
+    }
+
+    write_json(&paths.conflicts_path(), &conflicts).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn version(counts: &[(&str, u64)]) -> VersionVector {
+        counts
+            .iter()
+            .map(|(device, count)| (device.to_string(), *count))
+            .collect()
+    }
+
+    fn marker(id: &str, label: &str) -> Marker {
+        Marker {
+            id: id.to_string(),
+            timestamp: "2026-01-01T10:00:00Z".parse().unwrap(),
+            label: label.to_string(),
+            notes: None,
+            category: None,
+            recording_offset_ms: None,
+            elapsed_ms: Some(0),
+            duration_ms: None,
+        }
+    }
+
+    fn session(markers: Vec<Marker>) -> Session {
+        let mut session: Session = serde_json::from_value(json!({
+            "id": "session",
+            "start_time": "2026-01-01T10:00:00Z",
+            "end_time": "2026-01-01T11:00:00Z",
+            "markers": [],
+            "name": "Episode 1",
+            "description": null,
+        }))
+        .unwrap();
+        session.markers = markers;
+        session
+    }
+
+    fn labels(session: &Session) -> Vec<(&str, &str)> {
+        let mut labels: Vec<(&str, &str)> = session
+            .markers
+            .iter()
+            .map(|marker| (marker.id.as_str(), marker.label.as_str()))
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    #[test]
+    fn includes_needs_every_change_of_every_device() {
+        let a = version(&[("one", 2), ("two", 1)]);
+
+        assert!(includes(&a, &version(&[("one", 2)])));
+        assert!(includes(&a, &version(&[])));
+        assert!(!includes(&a, &version(&[("one", 3)])));
+        assert!(!includes(&a, &version(&[("three", 1)])));
+        assert!(!includes(&version(&[]), &a));
+    }
+
+    #[test]
+    fn merge_value_takes_the_side_that_changed() {
+        assert_eq!(merge_value(Some(&1), &1, &2), Some(2));
+        assert_eq!(merge_value(Some(&1), &2, &1), Some(2));
+        assert_eq!(merge_value(Some(&1), &2, &2), Some(2));
+        assert_eq!(merge_value(Some(&1), &2, &3), None);
+        assert_eq!(merge_value(None, &2, &2), Some(2));
+        assert_eq!(merge_value(None, &2, &3), None);
+    }
+
+    #[test]
+    fn concurrent_edits_of_different_markers_are_merged() {
+        let base = session(vec![marker("a", "Intro"), marker("b", "Guest")]);
+        let winner = session(vec![marker("a", "Cold open"), marker("b", "Guest")]);
+        let loser = session(vec![
+            marker("a", "Intro"),
+            marker("b", "Guest joins"),
+            marker("c", "Outro"),
+        ]);
+        let mut conflicts = Vec::new();
+
+        let merged = merge(Some(&base), &winner, &loser, "loser", &mut conflicts);
+
+        assert_eq!(
+            labels(&merged),
+            [("a", "Cold open"), ("b", "Guest joins"), ("c", "Outro")]
+        );
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn marker_edited_on_both_keeps_the_winner() {
+        let base = session(vec![marker("a", "Intro")]);
+        let winner = session(vec![marker("a", "Cold open")]);
+        let loser = session(vec![marker("a", "Teaser")]);
+        let mut conflicts = Vec::new();
+
+        let merged = merge(Some(&base), &winner, &loser, "loser", &mut conflicts);
+
+        assert_eq!(labels(&merged), [("a", "Cold open")]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].discarded_device, "loser");
+        match &conflicts[0].change {
+            ConflictingChange::Marker { kept, discarded } => {
+                assert_eq!(kept.as_ref().unwrap().label, "Cold open");
+                assert_eq!(discarded.as_ref().unwrap().label, "Teaser");
+            }
+            change => panic!("unexpected conflict {:?}", change),
+        }
+    }
+
+    #[test]
+    fn edit_against_delete_is_a_conflict() {
+        let base = session(vec![marker("a", "Intro")]);
+        let edited = session(vec![marker("a", "Cold open")]);
+        let deleted = session(vec![]);
+
+        let mut conflicts = Vec::new();
+        let merged = merge(Some(&base), &deleted, &edited, "loser", &mut conflicts);
+        assert!(merged.markers.is_empty());
+        assert!(matches!(
+            &conflicts[..],
+            [SyncConflict {
+                change: ConflictingChange::Marker {
+                    kept: None,
+                    discarded: Some(_),
+                },
+                ..
+            }]
+        ));
+
+        let mut conflicts = Vec::new();
+        let merged = merge(Some(&base), &edited, &deleted, "loser", &mut conflicts);
+        assert_eq!(labels(&merged), [("a", "Cold open")]);
+        assert_eq!(conflicts.len(), 1);
+    }
+
+    #[test]
+    fn delete_of_an_unchanged_marker_is_merged() {
+        let base = session(vec![marker("a", "Intro"), marker("b", "Guest")]);
+        let winner = session(vec![marker("a", "Intro"), marker("b", "Guest")]);
+        let loser = session(vec![marker("b", "Guest")]);
+        let mut conflicts = Vec::new();
+
+        let merged = merge(Some(&base), &winner, &loser, "loser", &mut conflicts);
+
+        assert_eq!(labels(&merged), [("b", "Guest")]);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn without_a_base_differences_are_conflicts() {
+        let winner = session(vec![marker("a", "Intro"), marker("b", "Guest")]);
+        let mut loser = session(vec![marker("a", "Intro"), marker("c", "Outro")]);
+        loser.name = Some("Episode 2".to_string());
+        let mut conflicts = Vec::new();
+
+        let merged = merge(None, &winner, &loser, "loser", &mut conflicts);
+
+        // Whether a marker missing on one side was added or deleted is unknown
+        assert_eq!(labels(&merged), [("a", "Intro"), ("b", "Guest")]);
+        assert_eq!(merged.name.as_deref(), Some("Episode 1"));
+        assert_eq!(conflicts.len(), 3);
+        assert!(conflicts
+            .iter()
+            .any(|conflict| matches!(conflict.change, ConflictingChange::Details { .. })));
+    }
+}
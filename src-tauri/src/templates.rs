@@ -0,0 +1,107 @@
+//! Session templates: presets for the kinds of sessions that are run again and
+//! again, like podcast episodes or interviews.
+//!
+//! A template names new sessions from a pattern, tags them, offers marker
+//! categories and extra marker hotkeys, and picks the export format used when
+//! none is given. Sessions can be started from a template with the
+//! `start_session` command, the tray menu or the template's own hotkey.
+
+use crate::{
+    session::{ActiveSession, SessionDetails},
+    storage::{ExportFormat, Storage},
+};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionTemplate {
+    /// Empty until settings give the template its ID, see `Settings::load`.
+    pub id: String,
+    pub name: String,
+    /// Name given to new sessions. `{template}`, `{date}`, `{time}` and
+    /// `{counter}` (sessions from this template so far, plus one) are replaced.
+    pub name_pattern: String,
+    pub tags: Vec<String>,
+    /// Categories offered for markers in sessions from this template.
+    pub marker_categories: Vec<String>,
+    /// Extra marker hotkeys, active while a session from this template runs.
+    pub marker_hotkeys: Vec<MarkerHotkey>,
+    /// Format used when a session from this template is exported without one.
+    pub export_format: Option<ExportFormat>,
+    /// Shortcut that starts a session from this template, e.g. `Alt+Numpad4`.
+    pub hotkey: Option<String>,
+}
+
+impl Default for SessionTemplate {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            name_pattern: "{template} {date}".to_string(),
+            tags: Vec::new(),
+            marker_categories: Vec::new(),
+            marker_hotkeys: Vec::new(),
+            export_format: None,
+            hotkey: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarkerHotkey {
+    pub shortcut: String,
+    pub label: String,
+    pub category: Option<String>,
+}
+
+impl SessionTemplate {
+    /// Fills in the name pattern for the `counter`th session from this template.
+    pub fn session_name(&self, counter: usize) -> String {
+        let now = Local::now();
+        self.name_pattern
+            .replace("{template}", &self.name)
+            .replace("{date}", &now.format("%Y-%m-%d").to_string())
+            .replace("{time}", &now.format("%H:%M").to_string())
+            .replace("{counter}", &counter.to_string())
+    }
+
+    /// A new session named and tagged by this template.
+    pub fn new_session(&self, storage: &Storage) -> ActiveSession {
+        let previous = match storage.list_sessions() {
+            Ok(sessions) => sessions
+                .iter()
+                .filter(|session| session.template_id.as_deref() == Some(self.id.as_str()))
+                .count(),
+            Err(err) => {
+                log::warn!("Failed to count sessions of template {}: {:?}", self.name, err);
+                0
+            }
+        };
+
+        let mut session = ActiveSession::new();
+        session.template_id = Some(self.id.clone());
+        session.set_details(SessionDetails {
+            name: Some(self.session_name(previous + 1)),
+            description: None,
+            tags: self.tags.clone(),
+        });
+        session
+    }
+}
+
+/// Gives templates without an ID a new one. Returns whether any was missing.
+pub fn assign_ids(templates: &mut [SessionTemplate]) -> bool {
+    let mut assigned = false;
+    for template in templates.iter_mut().filter(|template| template.id.is_empty()) {
+        template.id = Uuid::new_v4().to_string();
+        assigned = true;
+    }
+    assigned
+}
+
+/// The template with `id`, if it still exists.
+pub fn find<'a>(templates: &'a [SessionTemplate], id: &str) -> Option<&'a SessionTemplate> {
+    templates.iter().find(|template| template.id == id)
+}
@@ -0,0 +1,358 @@
+//! SMPTE timecode for marker times, at the frame rates editors work in.
+//!
+//! Media times are converted to a frame count at the real frame rate (e.g.
+//! 30000/1001 for 29.97) and then labelled with the nominal timebase, using
+//! drop-frame numbering where the rate calls for it, so the timecodes line up
+//! with what NLEs display for the same recording.
+
+use crate::session::{Marker, Session};
+use anyhow::{bail, Context, Result};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FrameRate {
+    #[serde(rename = "23.976")]
+    Fps23_976,
+    #[serde(rename = "24")]
+    Fps24,
+    #[serde(rename = "25")]
+    Fps25,
+    #[serde(rename = "29.97")]
+    Fps29_97,
+    #[serde(rename = "29.97df")]
+    Fps29_97Df,
+    #[default]
+    #[serde(rename = "30")]
+    Fps30,
+    #[serde(rename = "50")]
+    Fps50,
+    #[serde(rename = "59.94")]
+    Fps59_94,
+    #[serde(rename = "59.94df")]
+    Fps59_94Df,
+    #[serde(rename = "60")]
+    Fps60,
+}
+
+impl FrameRate {
+    /// The exact rate as a fraction of frames per second.
+    pub fn ratio(&self) -> (i64, i64) {
+        match self {
+            FrameRate::Fps23_976 => (24000, 1001),
+            FrameRate::Fps24 => (24, 1),
+            FrameRate::Fps25 => (25, 1),
+            FrameRate::Fps29_97 | FrameRate::Fps29_97Df => (30000, 1001),
+            FrameRate::Fps30 => (30, 1),
+            FrameRate::Fps50 => (50, 1),
+            FrameRate::Fps59_94 | FrameRate::Fps59_94Df => (60000, 1001),
+            FrameRate::Fps60 => (60, 1),
+        }
+    }
+
+    /// Frames per timecode second, i.e. the rate rounded to a whole number.
+    pub fn timebase(&self) -> i64 {
+        let (numerator, denominator) = self.ratio();
+        (numerator + denominator / 2) / denominator
+    }
+
+    pub fn is_drop_frame(&self) -> bool {
+        matches!(self, FrameRate::Fps29_97Df | FrameRate::Fps59_94Df)
+    }
+
+    /// Frame numbers skipped at the start of each minute except every tenth.
+    fn dropped_frames(&self) -> i64 {
+        if self.is_drop_frame() {
+            self.timebase() / 15
+        } else {
+            0
+        }
+    }
+
+    /// Whole frames elapsed after `duration`; negative durations round down.
+    pub fn frames_in(&self, duration: Duration) -> i64 {
+        let (numerator, denominator) = self.ratio();
+        let millis = duration.num_milliseconds() as i128;
+        (millis * numerator as i128).div_euclid(denominator as i128 * 1000) as i64
+    }
+
+    fn frames_per_day(&self) -> i64 {
+        let timebase = self.timebase();
+        let dropped_per_day = self.dropped_frames() * 9 * 6 * 24;
+        timebase * 86_400 - dropped_per_day
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            FrameRate::Fps23_976 => "23.976",
+            FrameRate::Fps24 => "24",
+            FrameRate::Fps25 => "25",
+            FrameRate::Fps29_97 => "29.97 NDF",
+            FrameRate::Fps29_97Df => "29.97 DF",
+            FrameRate::Fps30 => "30",
+            FrameRate::Fps50 => "50",
+            FrameRate::Fps59_94 => "59.94 NDF",
+            FrameRate::Fps59_94Df => "59.94 DF",
+            FrameRate::Fps60 => "60",
+        };
+        write!(f, "{} fps", label)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: i64,
+    pub minutes: i64,
+    pub seconds: i64,
+    pub frames: i64,
+    pub rate: FrameRate,
+}
+
+impl Timecode {
+    /// The timecode of the `frame`th frame, wrapping at 24 hours.
+    pub fn from_frames(frame: i64, rate: FrameRate) -> Self {
+        let timebase = rate.timebase();
+        let mut frame = frame.rem_euclid(rate.frames_per_day());
+
+        let drop = rate.dropped_frames();
+        if drop > 0 {
+            let frames_per_minute = timebase * 60 - drop;
+            let frames_per_ten_minutes = frames_per_minute * 10 + drop;
+
+            let ten_minutes = frame / frames_per_ten_minutes;
+            let remainder = frame % frames_per_ten_minutes;
+
+            frame += drop * 9 * ten_minutes;
+            if remainder > drop {
+                frame += drop * ((remainder - drop) / frames_per_minute);
+            }
+        }
+
+        Self {
+            hours: frame / (timebase * 3600),
+            minutes: (frame / (timebase * 60)) % 60,
+            seconds: (frame / timebase) % 60,
+            frames: frame % timebase,
+            rate,
+        }
+    }
+
+    pub fn to_frames(&self) -> i64 {
+        let timebase = self.rate.timebase();
+        let total_minutes = self.hours * 60 + self.minutes;
+        let nominal = (total_minutes * 60 + self.seconds) * timebase + self.frames;
+        nominal - self.rate.dropped_frames() * (total_minutes - total_minutes / 10)
+    }
+
+    /// Parses `HH:MM:SS:FF` (or `HH:MM:SS;FF` for drop-frame) at `rate`.
+    pub fn parse(value: &str, rate: FrameRate) -> Result<Self> {
+        let parts: Vec<&str> = value.trim().split([':', ';', '.']).collect();
+        if parts.len() != 4 {
+            bail!("Invalid timecode {:?}, expected HH:MM:SS:FF", value);
+        }
+
+        let mut fields = [0i64; 4];
+        for (field, part) in fields.iter_mut().zip(&parts) {
+            *field = part
+                .parse()
+                .with_context(|| format!("Invalid timecode {:?}", value))?;
+        }
+        let [hours, minutes, seconds, frames] = fields;
+
+        if !(0..24).contains(&hours)
+            || !(0..60).contains(&minutes)
+            || !(0..60).contains(&seconds)
+            || !(0..rate.timebase()).contains(&frames)
+        {
+            bail!("Timecode {:?} is out of range", value);
+        }
+
+        if minutes % 10 != 0 && seconds == 0 && frames < rate.dropped_frames() {
+            bail!("Timecode {:?} is skipped in drop-frame numbering", value);
+        }
+
+        Ok(Self {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            rate,
+        })
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.rate.is_drop_frame() { ';' } else { ':' };
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours, self.minutes, self.seconds, separator, self.frames
+        )
+    }
+}
+
+/// The frame rate used for `session`'s timecodes.
+pub fn session_rate(session: &Session, default_rate: FrameRate) -> FrameRate {
+    session.frame_rate.unwrap_or(default_rate)
+}
+
+/// First frame of the recording, from the session's start timecode.
+fn start_frame(session: &Session, rate: FrameRate) -> i64 {
+    match &session.start_timecode {
+        Some(start) => match Timecode::parse(start, rate) {
+            Ok(timecode) => timecode.to_frames(),
+            Err(err) => {
+                log::warn!("Ignoring start timecode of session {}: {:?}", session.id, err);
+                0
+            }
+        },
+        None => 0,
+    }
+}
+
+/// Timecode of `marker` in the recording, at the session's frame rate.
+pub fn marker_timecode(session: &Session, marker: &Marker, default_rate: FrameRate) -> Timecode {
+    media_timecode(session, session.media_time(marker), default_rate)
+}
+
+/// Timecode of `media_time` in the recording, at the session's frame rate.
+pub fn media_timecode(
+    session: &Session,
+    media_time: Duration,
+    default_rate: FrameRate,
+) -> Timecode {
+    let rate = session_rate(session, default_rate);
+    let frame = start_frame(session, rate) + rate.frames_in(media_time);
+    Timecode::from_frames(frame, rate)
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn set_session_timecode(
+    session_id: String,
+    frame_rate: Option<FrameRate>,
+    start_timecode: Option<String>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    let default_rate = {
+        let settings = app_state.settings.lock().map_err(|e| e.to_string())?;
+        settings.default_frame_rate
+    };
+
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    let mut session = storage
+        .get_session(&session_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Session with ID {} not found", session_id))?;
+
+    if let Some(start) = &start_timecode {
+        Timecode::parse(start, frame_rate.unwrap_or(default_rate)).map_err(|e| e.to_string())?;
+    }
+
+    session.frame_rate = frame_rate;
+    session.start_timecode = start_timecode;
+
+    storage.save_session(&session).map_err(|e| e.to_string())?;
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DROP_FRAME_RATES: [FrameRate; 2] = [FrameRate::Fps29_97Df, FrameRate::Fps59_94Df];
+
+    fn timecode(frame: i64, rate: FrameRate) -> String {
+        Timecode::from_frames(frame, rate).to_string()
+    }
+
+    #[test]
+    fn drop_frame_skips_frame_numbers_at_minutes() {
+        let rate = FrameRate::Fps29_97Df;
+        assert_eq!(timecode(1799, rate), "00:00:59;29");
+        assert_eq!(timecode(1800, rate), "00:01:00;02");
+        assert_eq!(timecode(3597, rate), "00:01:59;29");
+        assert_eq!(timecode(3598, rate), "00:02:00;02");
+
+        let rate = FrameRate::Fps59_94Df;
+        assert_eq!(timecode(3599, rate), "00:00:59;59");
+        assert_eq!(timecode(3600, rate), "00:01:00;04");
+    }
+
+    #[test]
+    fn drop_frame_keeps_frame_numbers_at_ten_minutes() {
+        let rate = FrameRate::Fps29_97Df;
+        assert_eq!(timecode(17_981, rate), "00:09:59;29");
+        assert_eq!(timecode(17_982, rate), "00:10:00;00");
+        assert_eq!(timecode(17_983, rate), "00:10:00;01");
+        assert_eq!(timecode(19_781, rate), "00:10:59;29");
+        assert_eq!(timecode(19_782, rate), "00:11:00;02");
+
+        let rate = FrameRate::Fps59_94Df;
+        assert_eq!(timecode(35_963, rate), "00:09:59;59");
+        assert_eq!(timecode(35_964, rate), "00:10:00;00");
+        assert_eq!(timecode(39_563, rate), "00:10:59;59");
+        assert_eq!(timecode(39_564, rate), "00:11:00;04");
+    }
+
+    #[test]
+    fn drop_frame_round_trips_through_frames_and_text() {
+        for rate in DROP_FRAME_RATES {
+            // Every frame of the first 21 minutes, then samples up to a day
+            let frames = (0..rate.frames_in(Duration::minutes(21)))
+                .chain((0..rate.frames_per_day()).step_by(997));
+
+            for frame in frames {
+                let timecode = Timecode::from_frames(frame, rate);
+                assert_eq!(timecode.to_frames(), frame, "{} at {}", timecode, rate);
+
+                let parsed = Timecode::parse(&timecode.to_string(), rate).unwrap();
+                assert_eq!(parsed, timecode);
+            }
+        }
+    }
+
+    #[test]
+    fn drop_frame_tracks_the_clock() {
+        for rate in DROP_FRAME_RATES {
+            let frame = rate.frames_in(Duration::hours(1));
+            assert_eq!(timecode(frame, rate), "01:00:00;00", "at {}", rate);
+        }
+    }
+
+    #[test]
+    fn drop_frame_wraps_at_a_day() {
+        for rate in DROP_FRAME_RATES {
+            let frames_per_day = rate.frames_per_day();
+            assert_eq!(timecode(frames_per_day, rate), "00:00:00;00");
+            assert_eq!(
+                Timecode::from_frames(frames_per_day - 1, rate).to_frames(),
+                frames_per_day - 1
+            );
+            assert_eq!(
+                Timecode::from_frames(-1, rate),
+                Timecode::from_frames(frames_per_day - 1, rate)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_rejects_dropped_frame_numbers() {
+        let rate = FrameRate::Fps29_97Df;
+        assert!(Timecode::parse("00:01:00;00", rate).is_err());
+        assert!(Timecode::parse("00:01:00;01", rate).is_err());
+        assert!(Timecode::parse("00:01:00;02", rate).is_ok());
+        assert!(Timecode::parse("00:10:00;00", rate).is_ok());
+
+        let rate = FrameRate::Fps59_94Df;
+        assert!(Timecode::parse("00:01:00;03", rate).is_err());
+        assert!(Timecode::parse("00:01:00;04", rate).is_ok());
+
+        // Non-drop rates number every frame
+        assert!(Timecode::parse("00:01:00:00", FrameRate::Fps29_97).is_ok());
+    }
+}
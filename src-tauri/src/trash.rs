@@ -0,0 +1,133 @@
+//! Deleting sessions into a per-project trash, restoring them and purging it.
+//!
+//! Trashed sessions live in the `trash/` folder of their project until they
+//! are restored or purged. Sessions older than the retention period are
+//! purged at startup and then once an hour.
+
+use crate::{projects, session::Session, storage, AppState};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, time::Duration as StdDuration};
+use tauri::{AppHandle, Manager};
+
+const PURGE_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrashSettings {
+    /// Days a session stays in the trash before it is purged; 0 keeps it forever.
+    pub retention_days: u32,
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+/// Deletes sessions in `sessions_dir`'s trash that were trashed more than
+/// `retention_days` ago. Returns how many were purged.
+fn purge_expired_in(sessions_dir: &Path, retention_days: u32) -> Result<usize> {
+    let cutoff = Utc::now() - Duration::days(retention_days as i64);
+    let trash_dir = storage::trash_dir(sessions_dir);
+    let mut purged = 0;
+
+    for session in storage::list_sessions_in(&trash_dir)? {
+        // Sessions without a timestamp were trashed by hand; leave them be
+        if session.trashed_at.is_some_and(|trashed_at| trashed_at < cutoff) {
+            storage::remove_session_file(&storage::session_path_in(&trash_dir, &session.id))?;
+            purged += 1;
+        }
+    }
+
+    Ok(purged)
+}
+
+/// Purges expired sessions from the trash of every project.
+pub fn purge_expired(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+
+    let retention_days = state.settings.lock().unwrap().trash.retention_days;
+    if retention_days == 0 {
+        return;
+    }
+
+    let storage = state.storage.lock().unwrap();
+    let projects = state.projects.lock().unwrap();
+
+    for project in &projects.projects {
+        let sessions_dir = projects::sessions_dir(storage.data_dir(), &project.id);
+        match purge_expired_in(&sessions_dir, retention_days) {
+            Ok(0) => {}
+            Ok(purged) => log::info!("Purged {} sessions from the trash of {}", purged, project.name),
+            Err(err) => log::error!("Failed to purge trash of {}: {:?}", project.name, err),
+        }
+    }
+}
+
+/// Purges expired sessions now and then every hour.
+pub fn spawn_purger(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            purge_expired(&app_handle);
+        }
+    });
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn delete_session(
+    id: String,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<(), String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    storage.trash_session(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_trashed_sessions(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<Session>, String> {
+    let storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    storage.list_trashed_sessions().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn restore_session(
+    id: String,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Session, String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+    storage.restore_trashed_session(&id).map_err(|e| e.to_string())
+}
+
+/// Permanently deletes the given sessions from the trash, or all of it if
+/// `ids` is `None`. Returns how many were deleted.
+#[tauri::command]
+pub async fn purge_trash(
+    ids: Option<Vec<String>>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<usize, String> {
+    let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+
+    let ids = match ids {
+        Some(ids) => ids,
+        None => storage
+            .list_trashed_sessions()
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|session| session.id)
+            .collect(),
+    };
+
+    for id in &ids {
+        storage
+            .purge_trashed_session(id)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(ids.len())
+}
@@ -1,9 +1,16 @@
 use crate::AppState;
 use tauri::{
-    menu::{Menu, MenuEvent, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, Submenu},
     AppHandle, Manager,
 };
 
+pub const TRAY_ID: &str = "main";
+
+/// Prefix of the menu item IDs that start a session from a template.
+const TEMPLATE_ITEM_PREFIX: &str = "template:";
+/// Prefix of the menu item IDs that switch to a project.
+const PROJECT_ITEM_PREFIX: &str = "project:";
+
 pub fn create_tray_menu(app: &AppHandle) -> tauri::menu::Menu<tauri::Wry> {
     let show_item =
         MenuItem::with_id(app, "show", "Open Dibikaandaagozi", true, None::<&str>).unwrap();
@@ -24,9 +31,52 @@ pub fn create_tray_menu(app: &AppHandle) -> tauri::menu::Menu<tauri::Wry> {
     )
     .unwrap();
 
+    let projects = app.state::<AppState>().projects.lock().unwrap().clone();
+    let project_menu = Submenu::with_id(app, "projects", "Project", true).unwrap();
+    for project in &projects.projects {
+        let item = CheckMenuItem::with_id(
+            app,
+            format!("{}{}", PROJECT_ITEM_PREFIX, project.id),
+            &project.name,
+            true,
+            project.id == projects.active,
+            None::<&str>,
+        )
+        .unwrap();
+        project_menu.append(&item).unwrap();
+    }
+    menu.insert(&project_menu, 1).unwrap();
+
+    let templates = app.state::<AppState>().settings.lock().unwrap().templates.clone();
+    if !templates.is_empty() {
+        let template_menu =
+            Submenu::with_id(app, "templates", "Start from Template", true).unwrap();
+        for template in &templates {
+            let item = MenuItem::with_id(
+                app,
+                format!("{}{}", TEMPLATE_ITEM_PREFIX, template.id),
+                &template.name,
+                true,
+                None::<&str>,
+            )
+            .unwrap();
+            template_menu.append(&item).unwrap();
+        }
+        menu.insert(&template_menu, 3).unwrap();
+    }
+
     menu
 }
 
+/// Rebuilds the tray menu, e.g. after the templates changed.
+pub fn refresh_tray_menu(app: &AppHandle) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        if let Err(err) = tray.set_menu(Some(create_tray_menu(app))) {
+            log::error!("Failed to update tray menu: {:?}", err);
+        }
+    }
+}
+
 pub fn handle_tray_event(app: &AppHandle, event: MenuEvent, app_state: &AppState) {
     match event.id.as_ref() {
         "quit" => {
@@ -47,6 +97,21 @@ pub fn handle_tray_event(app: &AppHandle, event: MenuEvent, app_state: &AppState
         "marker" => {
             crate::hotkey::handle_marker(app, &app_state.active_session);
         }
+        id if id.starts_with(TEMPLATE_ITEM_PREFIX) => {
+            let template_id = &id[TEMPLATE_ITEM_PREFIX.len()..];
+            let templates = app_state.settings.lock().unwrap().templates.clone();
+            if let Some(template) = crate::templates::find(&templates, template_id) {
+                crate::hotkey::start_template_session(app, template);
+            }
+        }
+        id if id.starts_with(PROJECT_ITEM_PREFIX) => {
+            let project_id = &id[PROJECT_ITEM_PREFIX.len()..];
+            if let Err(err) = crate::projects::switch_to(app, project_id) {
+                log::warn!("Failed to switch project: {:?}", err);
+                // Put the check mark back on the active project
+                refresh_tray_menu(app);
+            }
+        }
         _ => {}
     }
 }
@@ -0,0 +1,368 @@
+//! Undo and redo for marker and session edits.
+//!
+//! Every change is recorded with enough state to revert and reapply it, whether
+//! it was made in the active session or a stored one. Making a new change
+//! clears the redo history.
+
+use crate::{
+    events::{self, AppEvent},
+    session::{self, Marker, Session, SessionDetails},
+    storage::Storage,
+    AppState,
+};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter as _, Manager};
+
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug)]
+pub enum Change {
+    AddMarker {
+        session_id: String,
+        marker: Marker,
+    },
+    /// Several markers added at once, e.g. suggested highlights.
+    AddMarkers {
+        session_id: String,
+        markers: Vec<Marker>,
+    },
+    EditMarker {
+        session_id: String,
+        before: Marker,
+        after: Marker,
+    },
+    DeleteMarker {
+        session_id: String,
+        marker: Marker,
+    },
+    SetDetails {
+        session_id: String,
+        before: SessionDetails,
+        after: SessionDetails,
+    },
+    Split {
+        original_id: String,
+        parts: Vec<Session>,
+    },
+    Merge {
+        original_ids: Vec<String>,
+        merged: Session,
+    },
+}
+
+impl Change {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Change::AddMarker { .. } => "Add marker",
+            Change::AddMarkers { .. } => "Add markers",
+            Change::EditMarker { .. } => "Edit marker",
+            Change::DeleteMarker { .. } => "Delete marker",
+            Change::SetDetails { .. } => "Edit session details",
+            Change::Split { .. } => "Split session",
+            Change::Merge { .. } => "Merge sessions",
+        }
+    }
+
+    /// Events for a marker that reverting (or, with `reapply`, reapplying)
+    /// the change brings back, as adding it in the first place dispatched.
+    fn events(&self, reapply: bool) -> Vec<AppEvent> {
+        match self {
+            Change::AddMarker { session_id, marker } if reapply => {
+                vec![AppEvent::MarkerAdded {
+                    session_id: session_id.clone(),
+                    marker: marker.clone(),
+                }]
+            }
+            Change::DeleteMarker { session_id, marker } if !reapply => {
+                vec![AppEvent::MarkerAdded {
+                    session_id: session_id.clone(),
+                    marker: marker.clone(),
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn revert(&self, app_state: &AppState) -> Result<(), String> {
+        match self {
+            Change::AddMarker { session_id, marker } => {
+                remove_marker(app_state, session_id, &marker.id)
+            }
+            Change::AddMarkers {
+                session_id,
+                markers,
+            } => session::update_markers(app_state, session_id, |existing| {
+                existing.retain(|marker| !markers.iter().any(|added| added.id == marker.id));
+                Ok(())
+            }),
+            Change::EditMarker {
+                session_id, before, ..
+            } => replace_marker(app_state, session_id, before),
+            Change::DeleteMarker { session_id, marker } => {
+                session::update_markers(app_state, session_id, |markers| {
+                    session::insert_marker(markers, marker.clone());
+                    Ok(())
+                })
+            }
+            Change::SetDetails {
+                session_id, before, ..
+            } => session::update_details(app_state, session_id, before.clone()).map(|_| ()),
+            Change::Split { original_id, parts } => {
+                let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+                let mut stored = Vec::new();
+                for part in parts {
+                    if unchanged(&storage, part)? {
+                        stored.push(&part.id);
+                    }
+                }
+
+                storage
+                    .restore_archived_session(original_id)
+                    .map_err(|e| e.to_string())?;
+                for id in stored {
+                    storage.delete_session(id).map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+            Change::Merge {
+                original_ids,
+                merged,
+            } => {
+                let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+                let stored = unchanged(&storage, merged)?;
+
+                for id in original_ids {
+                    storage
+                        .restore_archived_session(id)
+                        .map_err(|e| e.to_string())?;
+                }
+                if stored {
+                    storage
+                        .delete_session(&merged.id)
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn apply(&self, app_state: &AppState) -> Result<(), String> {
+        match self {
+            Change::AddMarker { session_id, marker } => {
+                session::update_markers(app_state, session_id, |markers| {
+                    session::insert_marker(markers, marker.clone());
+                    Ok(())
+                })
+            }
+            Change::AddMarkers {
+                session_id,
+                markers,
+            } => session::update_markers(app_state, session_id, |existing| {
+                for marker in markers {
+                    session::insert_marker(existing, marker.clone());
+                }
+                Ok(())
+            }),
+            Change::EditMarker {
+                session_id, after, ..
+            } => replace_marker(app_state, session_id, after),
+            Change::DeleteMarker { session_id, marker } => {
+                remove_marker(app_state, session_id, &marker.id)
+            }
+            Change::SetDetails {
+                session_id, after, ..
+            } => session::update_details(app_state, session_id, after.clone()).map(|_| ()),
+            Change::Split { original_id, parts } => {
+                let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+                for part in parts {
+                    storage.save_session(part).map_err(|e| e.to_string())?;
+                }
+                storage
+                    .archive_session(original_id)
+                    .map_err(|e| e.to_string())
+            }
+            Change::Merge {
+                original_ids,
+                merged,
+            } => {
+                let mut storage = app_state.storage.lock().map_err(|e| e.to_string())?;
+                storage.save_session(merged).map_err(|e| e.to_string())?;
+                for id in original_ids {
+                    storage.archive_session(id).map_err(|e| e.to_string())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Whether `session` is still stored as it was made by a split or merge.
+/// Fails if it was edited since, as undoing would throw those edits away.
+fn unchanged(storage: &Storage, session: &Session) -> Result<bool, String> {
+    match storage.get_session(&session.id).map_err(|e| e.to_string())? {
+        Some(stored) if stored != *session => Err(format!(
+            "{} was changed since, so this can no longer be undone",
+            session.name.as_deref().unwrap_or(&session.id)
+        )),
+        Some(_) => Ok(true),
+        None => Ok(false),
+    }
+}
+
+fn remove_marker(app_state: &AppState, session_id: &str, marker_id: &str) -> Result<(), String> {
+    session::update_markers(app_state, session_id, |markers| {
+        let count = markers.len();
+        markers.retain(|marker| marker.id != marker_id);
+        if markers.len() == count {
+            return Err(format!("Marker with ID {} not found", marker_id));
+        }
+        Ok(())
+    })
+}
+
+fn replace_marker(
+    app_state: &AppState,
+    session_id: &str,
+    replacement: &Marker,
+) -> Result<(), String> {
+    session::update_markers(app_state, session_id, |markers| {
+        let marker = markers
+            .iter_mut()
+            .find(|marker| marker.id == replacement.id)
+            .ok_or_else(|| format!("Marker with ID {} not found", replacement.id))?;
+        *marker = replacement.clone();
+        Ok(())
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+/// What the next undo and redo would do, for the UI.
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoState {
+    pub undo: Option<&'static str>,
+    pub redo: Option<&'static str>,
+}
+
+impl UndoStack {
+    pub fn record(&mut self, change: Change) {
+        self.undo.push(change);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Forgets every change, e.g. when the sessions they refer to move away.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn state(&self) -> UndoState {
+        UndoState {
+            undo: self.undo.last().map(Change::description),
+            redo: self.redo.last().map(Change::description),
+        }
+    }
+}
+
+/// Records a change that was just made.
+pub fn record(app_state: &AppState, change: Change) {
+    match app_state.undo.lock() {
+        Ok(mut stack) => stack.record(change),
+        Err(err) => log::error!("Failed to record change for undo: {:?}", err),
+    }
+}
+
+pub fn emit_state(app_handle: &AppHandle, state: &UndoState) {
+    app_handle
+        .emit("undo-changed", state)
+        .unwrap_or_else(|e| log::error!("Failed to emit undo-changed event: {:?}", e));
+}
+
+/// Reverts the latest change. A change that fails to revert stays on the
+/// undo stack.
+pub fn undo(app_handle: &AppHandle) -> Result<UndoState, String> {
+    let app_state = app_handle.state::<AppState>();
+    let change = app_state.undo.lock().map_err(|e| e.to_string())?.undo.pop();
+    let change = change.ok_or_else(|| "Nothing to undo".to_string())?;
+
+    if let Err(err) = change.revert(&app_state) {
+        app_state.undo.lock().map_err(|e| e.to_string())?.undo.push(change);
+        return Err(err);
+    }
+    for event in change.events(false) {
+        events::dispatch(app_handle, event);
+    }
+
+    let mut stack = app_state.undo.lock().map_err(|e| e.to_string())?;
+    stack.redo.push(change);
+    let state = stack.state();
+    emit_state(app_handle, &state);
+    Ok(state)
+}
+
+/// Reapplies the latest undone change. A change that fails to apply stays on
+/// the redo stack.
+pub fn redo(app_handle: &AppHandle) -> Result<UndoState, String> {
+    let app_state = app_handle.state::<AppState>();
+    let change = app_state.undo.lock().map_err(|e| e.to_string())?.redo.pop();
+    let change = change.ok_or_else(|| "Nothing to redo".to_string())?;
+
+    if let Err(err) = change.apply(&app_state) {
+        app_state.undo.lock().map_err(|e| e.to_string())?.redo.push(change);
+        return Err(err);
+    }
+    for event in change.events(true) {
+        events::dispatch(app_handle, event);
+    }
+
+    let mut stack = app_state.undo.lock().map_err(|e| e.to_string())?;
+    stack.undo.push(change);
+    let state = stack.state();
+    emit_state(app_handle, &state);
+    Ok(state)
+}
+
+/// Undoes the latest change if it added a marker to the session with
+/// `session_id`. Returns whether it did.
+pub fn undo_last_marker(app_handle: &AppHandle, session_id: &str) -> Result<bool, String> {
+    let is_marker = {
+        let app_state = app_handle.state::<AppState>();
+        let stack = app_state.undo.lock().map_err(|e| e.to_string())?;
+        matches!(
+            stack.undo.last(),
+            Some(Change::AddMarker { session_id: id, .. }) if id == session_id
+        )
+    };
+
+    if !is_marker {
+        return Ok(false);
+    }
+
+    undo(app_handle).map(|_| true)
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn get_undo_state(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<UndoState, String> {
+    let stack = app_state.undo.lock().map_err(|e| e.to_string())?;
+    Ok(stack.state())
+}
+
+#[tauri::command]
+pub async fn undo_change(handle: tauri::AppHandle) -> Result<UndoState, String> {
+    undo(&handle)
+}
+
+#[tauri::command]
+pub async fn redo_change(handle: tauri::AppHandle) -> Result<UndoState, String> {
+    redo(&handle)
+}
@@ -0,0 +1,592 @@
+//! Outgoing webhooks for application events.
+//!
+//! Every event is POSTed as JSON to the configured targets, signed with
+//! HMAC-SHA256 when the target has a secret. Deliveries are queued on disk
+//! under `webhooks/` in the data directory and retried with exponential
+//! backoff, so failures survive restarts. Each attempt is written to a
+//! delivery log (`deliveries.json`) that the UI can read back. The queue
+//! (`queue.json`) holds the pending payloads, session names and marker notes
+//! included, unencrypted even when the session store is encrypted.
+
+use crate::{
+    events::{AppEvent, EventKind},
+    AppState,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+const MAX_ATTEMPTS: u32 = 8;
+const MAX_LOG_ENTRIES: usize = 500;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+pub const SIGNATURE_HEADER: &str = "X-MarkerMoment-Signature";
+pub const EVENT_HEADER: &str = "X-MarkerMoment-Event";
+pub const DELIVERY_HEADER: &str = "X-MarkerMoment-Delivery";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookTarget {
+    pub id: String,
+    pub url: String,
+    /// Shared secret used to sign payloads, sent as `sha256=<hex>`.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Events to deliver; an empty list delivers every event.
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WebhookTarget {
+    fn wants(&self, kind: EventKind) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&kind))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: String,
+    pub target_id: String,
+    pub event: EventKind,
+    pub body: String,
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    pub delivery_id: String,
+    pub target_id: String,
+    pub url: String,
+    pub event: EventKind,
+    pub attempt: u32,
+    pub timestamp: DateTime<Utc>,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub delivered: bool,
+    /// Set when the delivery will not be retried again.
+    pub gave_up: bool,
+}
+
+/// Pending deliveries and the delivery log, persisted under `webhooks/`.
+#[derive(Debug)]
+pub struct WebhookQueue {
+    dir: PathBuf,
+    pending: Vec<Delivery>,
+    log: Vec<DeliveryRecord>,
+    notify: Arc<Notify>,
+}
+
+impl WebhookQueue {
+    /// Loads the queue from `data_dir`. Unreadable files are logged and start empty
+    /// rather than keeping the app from starting.
+    pub fn load(data_dir: &Path) -> Self {
+        let dir = data_dir.join("webhooks");
+
+        let pending = read_json(&dir.join("queue.json")).unwrap_or_else(|err| {
+            log::error!("Failed to load webhook queue: {:?}", err);
+            Vec::new()
+        });
+        let log = read_json(&dir.join("deliveries.json")).unwrap_or_else(|err| {
+            log::error!("Failed to load webhook delivery log: {:?}", err);
+            Vec::new()
+        });
+
+        Self {
+            dir,
+            pending,
+            log,
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create webhook directory at {:?}", self.dir))?;
+
+        write_json(&self.dir.join("queue.json"), &self.pending)?;
+        write_json(&self.dir.join("deliveries.json"), &self.log)
+    }
+
+    pub fn enqueue(&mut self, target: &WebhookTarget, event: EventKind, body: &str) -> Result<()> {
+        let now = Utc::now();
+        self.pending.push(Delivery {
+            id: Uuid::new_v4().to_string(),
+            target_id: target.id.clone(),
+            event,
+            body: body.to_string(),
+            attempts: 0,
+            created_at: now,
+            next_attempt_at: now,
+        });
+        self.save()?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    fn due(&self, now: DateTime<Utc>) -> Vec<Delivery> {
+        self.pending
+            .iter()
+            .filter(|delivery| delivery.next_attempt_at <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Records the outcome of an attempt and reschedules or drops the delivery.
+    fn complete(&mut self, mut record: DeliveryRecord) -> Result<()> {
+        if let Some(index) = self
+            .pending
+            .iter()
+            .position(|delivery| delivery.id == record.delivery_id)
+        {
+            let delivery = &mut self.pending[index];
+            delivery.attempts = record.attempt;
+
+            if record.delivered || delivery.attempts >= MAX_ATTEMPTS {
+                record.gave_up = !record.delivered;
+                self.pending.remove(index);
+            } else {
+                delivery.next_attempt_at = Utc::now() + backoff(delivery.attempts);
+            }
+        }
+
+        self.log.push(record);
+        if self.log.len() > MAX_LOG_ENTRIES {
+            let excess = self.log.len() - MAX_LOG_ENTRIES;
+            self.log.drain(..excess);
+        }
+
+        self.save()
+    }
+
+    fn drop_delivery(&mut self, id: &str) -> Result<()> {
+        self.pending.retain(|delivery| delivery.id != id);
+        self.save()
+    }
+}
+
+/// 10s, 20s, 40s, ... capped at an hour.
+fn backoff(attempts: u32) -> ChronoDuration {
+    let seconds = 10i64.saturating_mul(1 << attempts.saturating_sub(1).min(16));
+    ChronoDuration::seconds(seconds.min(3600))
+}
+
+fn read_json<T: serde::de::DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let file = File::open(path).with_context(|| format!("Failed to open file at {:?}", path))?;
+    let reader = BufReader::new(file);
+    serde_json::from_reader(reader)
+        .with_context(|| format!("Failed to deserialize webhook data from {:?}", path))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    crate::storage::write_json_atomic(path, value)
+        .with_context(|| format!("Failed to save webhook data to {:?}", path))
+}
+
+/// Signature of `body` for the signature header, `sha256=<hex hmac>`.
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Queues `event` for every webhook target that subscribes to it.
+pub fn enqueue(app_handle: &AppHandle, event: &AppEvent) {
+    let state = app_handle.state::<AppState>();
+    let targets: Vec<WebhookTarget> = match state.settings.lock() {
+        Ok(settings) => settings
+            .webhooks
+            .iter()
+            .filter(|target| target.wants(event.kind()))
+            .cloned()
+            .collect(),
+        Err(err) => {
+            log::error!("Failed to read webhook settings: {:?}", err);
+            return;
+        }
+    };
+
+    if targets.is_empty() {
+        return;
+    }
+
+    let body = match serde_json::to_string(event) {
+        Ok(body) => body,
+        Err(err) => {
+            log::error!("Failed to serialize {} event for webhooks: {:?}", event.kind().name(), err);
+            return;
+        }
+    };
+
+    let mut queue = match state.webhooks.lock() {
+        Ok(queue) => queue,
+        Err(err) => {
+            log::error!("Failed to lock webhook queue: {:?}", err);
+            return;
+        }
+    };
+
+    for target in &targets {
+        if let Err(err) = queue.enqueue(target, event.kind(), &body) {
+            log::error!("Failed to queue webhook for {}: {:?}", target.url, err);
+        }
+    }
+}
+
+/// Spawns the background task that delivers queued webhooks.
+pub fn spawn_worker(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(err) => {
+                log::error!("Failed to create webhook HTTP client: {:?}", err);
+                return;
+            }
+        };
+
+        let notify = {
+            let state = app_handle.state::<AppState>();
+            let queue = state.webhooks.lock().unwrap();
+            queue.notify.clone()
+        };
+
+        loop {
+            deliver_due(&app_handle, &client).await;
+
+            tokio::select! {
+                _ = notify.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+async fn deliver_due(app_handle: &AppHandle, client: &reqwest::Client) {
+    let state = app_handle.state::<AppState>();
+
+    let due = match state.webhooks.lock() {
+        Ok(queue) => queue.due(Utc::now()),
+        Err(_) => return,
+    };
+
+    for delivery in due {
+        let target = state.settings.lock().ok().and_then(|settings| {
+            settings
+                .webhooks
+                .iter()
+                .find(|target| target.id == delivery.target_id)
+                .cloned()
+        });
+
+        // The target was removed since the event was queued
+        let Some(target) = target else {
+            if let Ok(mut queue) = state.webhooks.lock() {
+                queue
+                    .drop_delivery(&delivery.id)
+                    .unwrap_or_else(|e| log::error!("Failed to update webhook queue: {:?}", e));
+            }
+            continue;
+        };
+
+        let record = send(client, &target, &delivery).await;
+
+        if record.delivered {
+            log::info!("Delivered {} webhook to {}", delivery.event.name(), target.url);
+        } else {
+            log::warn!(
+                "Webhook delivery to {} failed (attempt {}): {}",
+                target.url,
+                record.attempt,
+                record.error.as_deref().unwrap_or("unknown error")
+            );
+        }
+
+        if let Ok(mut queue) = state.webhooks.lock() {
+            queue
+                .complete(record)
+                .unwrap_or_else(|e| log::error!("Failed to update webhook queue: {:?}", e));
+        }
+    }
+}
+
+async fn send(client: &reqwest::Client, target: &WebhookTarget, delivery: &Delivery) -> DeliveryRecord {
+    let mut request = client
+        .post(&target.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, delivery.event.name())
+        .header(DELIVERY_HEADER, &delivery.id)
+        .body(delivery.body.clone());
+
+    if let Some(secret) = &target.secret {
+        request = request.header(SIGNATURE_HEADER, sign(secret, &delivery.body));
+    }
+
+    let (status, error) = match request.send().await {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+        Ok(response) => (
+            Some(response.status().as_u16()),
+            Some(format!("Unexpected status {}", response.status())),
+        ),
+        Err(err) => (None, Some(err.to_string())),
+    };
+
+    DeliveryRecord {
+        delivery_id: delivery.id.clone(),
+        target_id: target.id.clone(),
+        url: target.url.clone(),
+        event: delivery.event,
+        attempt: delivery.attempts + 1,
+        timestamp: Utc::now(),
+        status,
+        delivered: error.is_none(),
+        error,
+        gave_up: false,
+    }
+}
+
+// Tauri command functions
+#[tauri::command]
+pub async fn get_webhook_deliveries(
+    limit: Option<usize>,
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<DeliveryRecord>, String> {
+    let queue = app_state.webhooks.lock().map_err(|e| e.to_string())?;
+
+    // Newest first
+    Ok(queue
+        .log
+        .iter()
+        .rev()
+        .take(limit.unwrap_or(MAX_LOG_ENTRIES))
+        .cloned()
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_pending_webhooks(
+    app_state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<Delivery>, String> {
+    let queue = app_state.webhooks.lock().map_err(|e| e.to_string())?;
+    Ok(queue.pending.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    const SECRET: &str = "key";
+    const BODY: &str = r#"{"event":"session-started"}"#;
+
+    /// A request as received by the local stand-in.
+    struct Received {
+        headers: Vec<(String, String)>,
+        body: String,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Accepts one HTTP request on a local port, answers it with `status` and
+    /// returns what was sent.
+    async fn stand_in(status: u16) -> (String, tokio::task::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut data = Vec::new();
+            let mut buffer = [0; 4096];
+            let header_end = loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                assert!(read > 0, "connection closed before the headers ended");
+                data.extend_from_slice(&buffer[..read]);
+                if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+                    break end + 4;
+                }
+            };
+
+            let head = String::from_utf8(data[..header_end].to_vec()).unwrap();
+            let headers: Vec<(String, String)> = head
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect();
+            let length: usize = headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .map_or(0, |(_, value)| value.parse().unwrap());
+
+            while data.len() < header_end + length {
+                let read = stream.read(&mut buffer).await.unwrap();
+                assert!(read > 0, "connection closed before the body ended");
+                data.extend_from_slice(&buffer[..read]);
+            }
+
+            let response = format!(
+                "HTTP/1.1 {} Stand-in\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+
+            Received {
+                headers,
+                body: String::from_utf8(data[header_end..header_end + length].to_vec()).unwrap(),
+            }
+        });
+
+        (url, server)
+    }
+
+    fn target(url: &str) -> WebhookTarget {
+        WebhookTarget {
+            id: "target".to_string(),
+            url: url.to_string(),
+            secret: Some(SECRET.to_string()),
+            events: Vec::new(),
+            enabled: true,
+        }
+    }
+
+    fn temp_data_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("markermoment-webhooks-{}", Uuid::new_v4()))
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn signature_is_hex_hmac_sha256() {
+        assert_eq!(
+            sign(SECRET, "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        let seconds: Vec<i64> = (1..=11).map(|attempts| backoff(attempts).num_seconds()).collect();
+        assert_eq!(
+            seconds,
+            [10, 20, 40, 80, 160, 320, 640, 1280, 2560, 3600, 3600]
+        );
+        assert_eq!(backoff(u32::MAX).num_seconds(), 3600);
+    }
+
+    #[tokio::test]
+    async fn delivery_is_signed() {
+        let (url, server) = stand_in(200).await;
+        let data_dir = temp_data_dir();
+        let mut queue = WebhookQueue::load(&data_dir);
+        queue
+            .enqueue(&target(&url), EventKind::SessionStarted, BODY)
+            .unwrap();
+
+        let delivery = queue.due(Utc::now()).pop().unwrap();
+        let record = send(&client(), &target(&url), &delivery).await;
+        let received = server.await.unwrap();
+
+        assert!(record.delivered);
+        assert_eq!(record.status, Some(200));
+        assert_eq!(received.body, BODY);
+        assert_eq!(
+            received.header(SIGNATURE_HEADER),
+            Some(sign(SECRET, BODY).as_str())
+        );
+        assert_eq!(received.header(EVENT_HEADER), Some("session-started"));
+        assert_eq!(received.header(DELIVERY_HEADER), Some(delivery.id.as_str()));
+
+        queue.complete(record).unwrap();
+        assert!(queue.pending.is_empty());
+        assert!(WebhookQueue::load(&data_dir).pending.is_empty());
+
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_retried_after_restart() {
+        let (url, server) = stand_in(500).await;
+        let data_dir = temp_data_dir();
+        let mut queue = WebhookQueue::load(&data_dir);
+        queue
+            .enqueue(&target(&url), EventKind::MarkerAdded, BODY)
+            .unwrap();
+
+        let delivery = queue.due(Utc::now()).pop().unwrap();
+        let record = send(&client(), &target(&url), &delivery).await;
+        server.await.unwrap();
+
+        assert!(!record.delivered);
+        assert_eq!(record.status, Some(500));
+        let before = Utc::now();
+        queue.complete(record).unwrap();
+
+        // Survives a restart, scheduled after the first backoff
+        let mut queue = WebhookQueue::load(&data_dir);
+        assert_eq!(queue.pending.len(), 1);
+        assert_eq!(queue.log.len(), 1);
+        let pending = queue.pending[0].clone();
+        assert_eq!(pending.id, delivery.id);
+        assert_eq!(pending.attempts, 1);
+        assert!(pending.next_attempt_at >= before + backoff(1));
+        assert!(pending.next_attempt_at <= Utc::now() + backoff(1));
+        assert!(queue.due(Utc::now()).is_empty());
+        assert_eq!(queue.due(pending.next_attempt_at).len(), 1);
+
+        // Nothing listens on the port any more, so the remaining attempts fail
+        for attempt in 2..=MAX_ATTEMPTS {
+            let delivery = queue.pending[0].clone();
+            let record = send(&client(), &target(&url), &delivery).await;
+            assert_eq!(record.attempt, attempt);
+            assert!(record.status.is_none());
+            queue.complete(record).unwrap();
+        }
+
+        assert!(queue.pending.is_empty());
+        assert!(queue.log.last().unwrap().gave_up);
+        assert!(WebhookQueue::load(&data_dir).pending.is_empty());
+
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
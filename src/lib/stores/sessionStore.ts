@@ -3,8 +3,9 @@ import { writable, derived, get } from 'svelte/store';
 // Define SessionType first
 export type SessionType = {
   id: string;
-  title: string;
-  description: string;
+  name?: string | null;
+  description?: string | null;
+  tags?: string[];
   start_time: string;
   end_time?: string;
   duration: number;